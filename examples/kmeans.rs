@@ -10,12 +10,13 @@ use gnuplot::{Figure, Color};
 use nalgebra::{Iterable};
 use std::ops::Index;
 
-use brasswheels::io::read_csv_f64;
+use brasswheels::io::{read_csv_f64, read_csv_labels};
 use brasswheels::kmeans::KMeans;
+use brasswheels::metrics::adjusted_rand_score;
 use brasswheels::pca::PCA;
 
 fn main() {
-    // cargo build --example kmeans
+    // cargo build --example kmeans --features plot
     // ./target/debug/examples/kmeans
 
     // http://aima.cs.berkeley.edu/data/iris.csv
@@ -28,13 +29,18 @@ fn main() {
     kmeans.fit(&dx);
 
     println!("各クラスタの中心");
-    for (_, cluster) in &kmeans.centroids {
-        println!("{:?}", cluster.centroid.at);
+    for c in 0..kmeans.nclusters {
+        println!("{:?}", kmeans.centroid(c).at);
     }
 
     let predicted = kmeans.predict(&dx);
     println!("結果\n{:?}", &predicted.at);
 
+    // 品種との比較
+    let mut reader = csv::Reader::from_file(path).unwrap().has_headers(false);
+    let (species, _) = read_csv_labels(&mut reader, 4);
+    println!("Adjusted Rand Index: {:?}", adjusted_rand_score(&species, &predicted));
+
     // 主成分分析
     let mut pca = PCA::new(4, true);
     pca.fit(&dx);
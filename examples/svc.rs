@@ -1,12 +1,10 @@
 extern crate csv;
-extern crate gnuplot;
 extern crate nalgebra;
 extern crate num;
 extern crate rand;
 
 extern crate brasswheels;
 
-use gnuplot::{Figure, Color};
 use nalgebra::{DVec, DMat, Iterable};
 use std::ops::Index;
 
@@ -0,0 +1,397 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, RowSlice};
+use std::collections::VecDeque;
+use std::f64;
+
+use super::kdtree::KDTree;
+use super::mathfunc::euc_dist;
+use super::mathfunc::distance::{Metric, pairwise_distances};
+
+/// DBSCAN (Density-Based Spatial Clustering of Applications with Noise)
+pub struct DBSCAN {
+    pub eps: f64,                           // radius of the neighborhood
+    pub min_samples: usize,                 // records in the neighborhood (including itself) of core records
+    pub labels: Vec<Option<usize>>,         // cluster of each record, None for noise
+    pub core_samples: Vec<usize>,           // row numbers of core records
+    pub nclusters: usize,
+}
+
+impl DBSCAN {
+
+    pub fn new(eps: f64, min_samples: usize) -> DBSCAN {
+        DBSCAN {
+            eps: eps,
+            min_samples: min_samples,
+            labels: vec![],
+            core_samples: vec![],
+            nclusters: 0,
+        }
+    }
+
+    /// fit using Euclid distance, neighbors are searched by k-d tree
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        let tree = KDTree::new(data);
+        let neighbors: Vec<Vec<usize>> = (0..data.nrows()).map(|r| {
+            tree.within_radius(&data.row_slice(r, 0, data.ncols()), self.eps)
+        }).collect();
+        self.fit_neighbors(neighbors);
+    }
+
+    /// fit using the specified metric
+    pub fn fit_with<M: Metric>(&mut self, data: &DMat<f64>, metric: M) {
+        let rows: Vec<DVec<f64>> = (0..data.nrows()).map(|r| data.row_slice(r, 0, data.ncols()))
+                                                    .collect();
+        let neighbors: Vec<Vec<usize>> = rows.iter().map(|a| {
+            (0..rows.len()).filter(|&j| metric.distance(a, &rows[j]) <= self.eps).collect::<Vec<usize>>()
+        }).collect();
+        self.fit_neighbors(neighbors);
+    }
+
+    /// expand clusters from core records in order of row numbers
+    fn fit_neighbors(&mut self, neighbors: Vec<Vec<usize>>) {
+        let n = neighbors.len();
+        let is_core: Vec<bool> = neighbors.iter().map(|v| v.len() >= self.min_samples).collect();
+
+        self.core_samples = (0..n).filter(|&i| is_core[i]).collect();
+        self.labels = vec![None; n];
+        self.nclusters = 0;
+
+        for i in 0..n {
+            if !is_core[i] || self.labels[i].is_some() {
+                continue;
+            }
+            let label = self.nclusters;
+            self.nclusters += 1;
+
+            self.labels[i] = Some(label);
+            let mut queue = VecDeque::new();
+            queue.push_back(i);
+            while let Some(p) = queue.pop_front() {
+                // only core records extend the cluster
+                if !is_core[p] {
+                    continue;
+                }
+                for &q in &neighbors[p] {
+                    if self.labels[q].is_none() {
+                        self.labels[q] = Some(label);
+                        queue.push_back(q);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// HDBSCAN (Hierarchical DBSCAN)
+///
+/// Builds single linkage tree on mutual reachability distances, condenses it by
+/// min_cluster_size and selects the clusters of excess of mass.
+pub struct HDBSCAN {
+    pub min_cluster_size: usize,
+    pub min_samples: usize,                 // neighbors (including itself) used for core distances
+    pub labels: Vec<Option<usize>>,         // cluster of each record, None for noise
+    pub nclusters: usize,
+}
+
+impl HDBSCAN {
+
+    pub fn new(min_cluster_size: usize) -> HDBSCAN {
+        HDBSCAN {
+            min_cluster_size: min_cluster_size,
+            min_samples: min_cluster_size,
+            labels: vec![],
+            nclusters: 0,
+        }
+    }
+
+    /// fit using Euclid distance, core distances are searched by k-d tree
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        let rows: Vec<DVec<f64>> = (0..data.nrows()).map(|r| data.row_slice(r, 0, data.ncols()))
+                                                    .collect();
+        let tree = KDTree::new(data);
+        let core: Vec<f64> = rows.iter().map(|row| {
+            match tree.nearest(row, self.min_samples).last() {
+                Some(&(_, d)) => d,
+                None => 0.
+            }
+        }).collect();
+        self.fit_core(&core, |i, j| euc_dist(&rows[i], &rows[j]));
+    }
+
+    /// fit using the specified metric
+    pub fn fit_with<M: Metric>(&mut self, data: &DMat<f64>, metric: M) {
+        let n = data.nrows();
+        let dist_mat = pairwise_distances(data, data, &metric);
+        let core: Vec<f64> = (0..n).map(|i| {
+            let mut dists: Vec<f64> = (0..n).map(|j| dist_mat[(i, j)]).collect();
+            dists.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            dists[self.min_samples.min(n) - 1]
+        }).collect();
+        self.fit_core(&core, |i, j| dist_mat[(i, j)]);
+    }
+
+    fn fit_core<F>(&mut self, core: &[f64], dist: F) where F: Fn(usize, usize) -> f64 {
+        let n = core.len();
+        self.labels = vec![None; n];
+        self.nclusters = 0;
+        if n < 2 {
+            return;
+        }
+
+        let tree = single_linkage(n, |i, j| dist(i, j).max(core[i]).max(core[j]));
+        let condensed = condense_tree(&tree, n, self.min_cluster_size);
+        let selected = select_clusters(&condensed, n);
+
+        // parent in the condensed tree
+        let mut parents = vec![0; n + condensed.len()];
+        for &(parent, child, _, _) in &condensed {
+            parents[child] = parent;
+        }
+
+        // records belong to the selected ancestor, label clusters in order of records
+        let mut labels: Vec<Option<usize>> = vec![None; n + condensed.len()];
+        for i in 0..n {
+            let mut c = parents[i];
+            loop {
+                if selected[c] {
+                    if labels[c].is_none() {
+                        labels[c] = Some(self.nclusters);
+                        self.nclusters += 1;
+                    }
+                    self.labels[i] = labels[c];
+                    break;
+                }
+                if c == n {
+                    break;
+                }
+                c = parents[c];
+            }
+        }
+    }
+}
+
+/// minimum spanning tree by Prim's algorithm, merges of (left, right, distance, size)
+///
+/// Merged cluster has id n + merge number, as the linkage matrix of SciPy.
+fn single_linkage<F>(n: usize, dist: F) -> Vec<(usize, usize, f64, usize)>
+    where F: Fn(usize, usize) -> f64 {
+
+    let mut in_tree = vec![false; n];
+    let mut nearest = vec![f64::INFINITY; n];
+    let mut from = vec![0; n];
+    let mut edges: Vec<(f64, usize, usize)> = vec![];
+
+    let mut current = 0;
+    in_tree[0] = true;
+    for _ in 1..n {
+        let mut next = n;
+        for j in 0..n {
+            if in_tree[j] {
+                continue;
+            }
+            let d = dist(current, j);
+            if d < nearest[j] {
+                nearest[j] = d;
+                from[j] = current;
+            }
+            if next == n || nearest[j] < nearest[next] {
+                next = j;
+            }
+        }
+        edges.push((nearest[next], from[next], next));
+        in_tree[next] = true;
+        current = next;
+    }
+    edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    // union-find of merged clusters
+    let mut parents: Vec<usize> = (0..(2 * n - 1)).collect();
+    let mut sizes = vec![1; 2 * n - 1];
+    let mut merges = vec![];
+    for (m, &(d, a, b)) in edges.iter().enumerate() {
+        let ra = find_root(&parents, a);
+        let rb = find_root(&parents, b);
+        let id = n + m;
+        parents[ra] = id;
+        parents[rb] = id;
+        sizes[id] = sizes[ra] + sizes[rb];
+        merges.push((ra, rb, d, sizes[id]));
+    }
+    return merges;
+}
+
+fn find_root(parents: &[usize], mut x: usize) -> usize {
+    while parents[x] != x {
+        x = parents[x];
+    }
+    return x;
+}
+
+/// condensed tree of (parent, child, lambda, child size)
+///
+/// Clusters are numbered from n (the root), records are numbered 0 to n - 1.
+/// Splits which leave less than min_cluster_size records are regarded as
+/// records falling out of the parent cluster.
+fn condense_tree(merges: &[(usize, usize, f64, usize)], n: usize,
+                 min_cluster_size: usize) -> Vec<(usize, usize, f64, usize)> {
+    let size = |node: usize| if node < n { 1 } else { merges[node - n].3 };
+
+    let root = 2 * n - 2;
+    let mut relabel = vec![0; 2 * n - 1];
+    relabel[root] = n;
+    let mut next_label = n + 1;
+    let mut condensed = vec![];
+
+    let mut queue = VecDeque::new();
+    queue.push_back(root);
+    while let Some(node) = queue.pop_front() {
+        if node < n {
+            continue;
+        }
+        let (left, right, d, _) = merges[node - n];
+        let lambda = if d > 0. { 1. / d } else { f64::INFINITY };
+        let parent = relabel[node];
+
+        if size(left) >= min_cluster_size && size(right) >= min_cluster_size {
+            // true split
+            for &child in &[left, right] {
+                relabel[child] = next_label;
+                condensed.push((parent, next_label, lambda, size(child)));
+                next_label += 1;
+                queue.push_back(child);
+            }
+        } else {
+            for &child in &[left, right] {
+                if size(child) >= min_cluster_size {
+                    // the cluster continues as the parent
+                    relabel[child] = parent;
+                    queue.push_back(child);
+                } else {
+                    for leaf in leaves(merges, n, child) {
+                        condensed.push((parent, leaf, lambda, 1));
+                    }
+                }
+            }
+        }
+    }
+    return condensed;
+}
+
+/// records under the node
+fn leaves(merges: &[(usize, usize, f64, usize)], n: usize, node: usize) -> Vec<usize> {
+    let mut found = vec![];
+    let mut stack = vec![node];
+    while let Some(x) = stack.pop() {
+        if x < n {
+            found.push(x);
+        } else {
+            stack.push(merges[x - n].0);
+            stack.push(merges[x - n].1);
+        }
+    }
+    return found;
+}
+
+/// whether each cluster of the condensed tree is selected, by excess of mass
+///
+/// The root is never selected.
+fn select_clusters(condensed: &[(usize, usize, f64, usize)], n: usize) -> Vec<bool> {
+    let nnodes = condensed.iter().fold(n + 1, |a, &(p, c, _, _)| a.max(p + 1).max(c + 1));
+
+    let mut birth = vec![0.; nnodes];
+    let mut children: Vec<Vec<usize>> = vec![vec![]; nnodes];
+    for &(parent, child, lambda, _) in condensed {
+        if child >= n {
+            birth[child] = lambda;
+            children[parent].push(child);
+        }
+    }
+    let mut stability = vec![0.; nnodes];
+    for &(parent, _, lambda, size) in condensed {
+        stability[parent] += (lambda - birth[parent]) * size as f64;
+    }
+
+    // children have larger numbers than their parents
+    let mut selected = vec![false; nnodes];
+    for c in ((n + 1)..nnodes).rev() {
+        let subtree = children[c].iter().fold(0., |a, &child| a + stability[child]);
+        if children[c].len() > 0 && subtree > stability[c] {
+            stability[c] = subtree;
+        } else {
+            selected[c] = true;
+            let mut stack = children[c].clone();
+            while let Some(x) = stack.pop() {
+                selected[x] = false;
+                stack.extend(children[x].iter().cloned());
+            }
+        }
+    }
+    return selected;
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMat;
+    use super::{DBSCAN, HDBSCAN};
+    use super::super::mathfunc::distance::{Euclidean, Chebyshev};
+
+    fn get_data() -> DMat<f64> {
+        // two blobs of 20 records on a grid and an outlier
+        return DMat::from_fn(41, 2, |i, j| {
+            let v = if j == 0 { (i % 5) as f64 } else { ((i / 5) % 4) as f64 };
+            if i < 20 { v } else if i < 40 { v + 20. } else { 50. }
+        });
+    }
+
+    #[test]
+    fn test_dbscan() {
+        let data = get_data();
+
+        let mut dbscan = DBSCAN::new(1.5, 4);
+        dbscan.fit(&data);
+        assert_eq!(dbscan.nclusters, 2);
+        for i in 0..40 {
+            let expected = if i < 20 { 0 } else { 1 };
+            assert_eq!(dbscan.labels[i], Some(expected));
+        }
+        assert_eq!(dbscan.labels[40], None);
+        // all records on the grid have 4 or more neighbors
+        assert_eq!(dbscan.core_samples, (0..40).collect::<Vec<usize>>());
+
+        // corners have 4 neighbors, edges 6 and inner records 9
+        let mut dbscan = DBSCAN::new(1.5, 5);
+        dbscan.fit_with(&data, Euclidean);
+        assert_eq!(dbscan.nclusters, 2);
+        assert_eq!(dbscan.core_samples.len(), 32);
+        assert_eq!(dbscan.labels[0], Some(0));
+        assert_eq!(dbscan.labels[40], None);
+
+        // Chebyshev distance 1 on the grid equals Euclid distance sqrt(2)
+        let mut dbscan = DBSCAN::new(1., 9);
+        dbscan.fit_with(&data, Chebyshev);
+        assert_eq!(dbscan.core_samples.len(), 12);
+
+        let mut dbscan2 = DBSCAN::new(2f64.sqrt(), 9);
+        dbscan2.fit(&data);
+        assert_eq!(dbscan2.labels, dbscan.labels);
+    }
+
+    #[test]
+    fn test_hdbscan() {
+        let data = get_data();
+
+        let mut hdbscan = HDBSCAN::new(5);
+        hdbscan.fit(&data);
+        assert_eq!(hdbscan.nclusters, 2);
+        for i in 0..40 {
+            let expected = if i < 20 { 0 } else { 1 };
+            assert_eq!(hdbscan.labels[i], Some(expected));
+        }
+        assert_eq!(hdbscan.labels[40], None);
+
+        let mut hdbscan2 = HDBSCAN::new(5);
+        hdbscan2.fit_with(&data, Euclidean);
+        assert_eq!(hdbscan2.labels, hdbscan.labels);
+    }
+}
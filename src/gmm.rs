@@ -0,0 +1,340 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, RowSlice};
+use std::f64;
+
+use super::kmeans::KMeans;
+
+/// Form of the covariance matrices
+pub enum CovarianceType {
+    Full,                   // each component has its own general covariance matrix
+    Diag,                   // each component has its own diagonal covariance matrix
+    Tied,                   // all components share the same general covariance matrix
+    Spherical,              // each component has its own single variance
+}
+
+/// Gaussian mixture model fitted by EM algorithm
+///
+/// Responsibilities are initialized from the labels of `KMeans`.
+pub struct GaussianMixture {
+    pub ncomponents: usize,
+    max_iter: usize,
+    pub covariance_type: CovarianceType,
+    pub tolerance: f64,                     // threshold of the change of mean log-likelihood
+    pub reg_covar: f64,                     // added to the diagonal of covariances
+    pub seed: Option<usize>,                // seed of KMeans initialization
+
+    pub weights: DVec<f64>,                 // mixing weights
+    pub means: DMat<f64>,                   // means of components as rows
+    pub covariances: Vec<DMat<f64>>,        // covariance matrix of each component
+    pub log_likelihood: f64,                // log-likelihood of the training data
+    pub n_iter: usize,
+    pub converged: bool,
+}
+
+impl GaussianMixture {
+
+    pub fn new(ncomponents: usize, max_iter: usize) -> GaussianMixture {
+        GaussianMixture {
+            ncomponents: ncomponents,
+            max_iter: max_iter,
+            covariance_type: CovarianceType::Full,
+            tolerance: 1e-3,
+            reg_covar: 1e-6,
+            seed: None,
+
+            weights: DVec::from_elem(1, 0.),
+            means: DMat::from_elem(1, 1, 0.),
+            covariances: vec![],
+            log_likelihood: f64::NEG_INFINITY,
+            n_iter: 0,
+            converged: false,
+        }
+    }
+
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        let n = data.nrows();
+        let k = self.ncomponents;
+        assert!(k <= n, "Number of components must be less than number of records");
+
+        // initial responsibilities from hard assignments of KMeans
+        let mut kmeans = KMeans::new(k, 100);
+        kmeans.seed = self.seed;
+        kmeans.fit(data);
+        let labels = kmeans.predict(data);
+        let resp = DMat::from_fn(n, k, |i, c| if labels[i] == c { 1. } else { 0. });
+        self.m_step(data, &resp);
+
+        self.converged = false;
+        self.n_iter = 0;
+        let mut current = f64::NEG_INFINITY;
+        for i in 0..self.max_iter {
+            let (resp, log_likelihood) = self.e_step(data);
+            self.m_step(data, &resp);
+
+            self.n_iter = i + 1;
+            let change = (log_likelihood - current) / n as f64;
+            current = log_likelihood;
+            if change.abs() < self.tolerance {
+                self.converged = true;
+                break;
+            }
+        }
+        self.log_likelihood = self.score(data);
+    }
+
+    /// responsibilities and log-likelihood
+    fn e_step(&self, data: &DMat<f64>) -> (DMat<f64>, f64) {
+        let log_prob = self.weighted_log_prob(data);
+        let mut resp = DMat::from_elem(data.nrows(), self.ncomponents, 0.);
+        let mut log_likelihood = 0.;
+        for i in 0..data.nrows() {
+            let lse = logsumexp(&log_prob, i);
+            for c in 0..self.ncomponents {
+                resp[(i, c)] = (log_prob[(i, c)] - lse).exp();
+            }
+            log_likelihood += lse;
+        }
+        return (resp, log_likelihood);
+    }
+
+    /// update weights, means and covariances from responsibilities
+    fn m_step(&mut self, data: &DMat<f64>, resp: &DMat<f64>) {
+        let n = data.nrows();
+        let d = data.ncols();
+        let k = self.ncomponents;
+
+        // avoid division by zero for empty components
+        let nk = DVec::from_fn(k, |c| (0..n).fold(10. * f64::EPSILON, |a, i| a + resp[(i, c)]));
+        self.weights = DVec::from_fn(k, |c| nk[c] / n as f64);
+        self.means = DMat::from_fn(k, d, |c, j| {
+            (0..n).fold(0., |a, i| a + resp[(i, c)] * data[(i, j)]) / nk[c]
+        });
+
+        // weighted scatter matrix of each component
+        let scatters: Vec<DMat<f64>> = (0..k).map(|c| {
+            DMat::from_fn(d, d, |p, q| {
+                (0..n).fold(0., |a, i| {
+                    a + resp[(i, c)] * (data[(i, p)] - self.means[(c, p)])
+                                     * (data[(i, q)] - self.means[(c, q)])
+                })
+            })
+        }).collect();
+
+        let reg = self.reg_covar;
+        self.covariances = match self.covariance_type {
+            CovarianceType::Full => {
+                (0..k).map(|c| DMat::from_fn(d, d, |p, q| {
+                    scatters[c][(p, q)] / nk[c] + if p == q { reg } else { 0. }
+                })).collect()
+            },
+            CovarianceType::Diag => {
+                (0..k).map(|c| DMat::from_fn(d, d, |p, q| {
+                    if p == q { scatters[c][(p, p)] / nk[c] + reg } else { 0. }
+                })).collect()
+            },
+            CovarianceType::Tied => {
+                let tied = DMat::from_fn(d, d, |p, q| {
+                    scatters.iter().fold(0., |a, s| a + s[(p, q)]) / n as f64
+                    + if p == q { reg } else { 0. }
+                });
+                (0..k).map(|_| tied.clone()).collect()
+            },
+            CovarianceType::Spherical => {
+                (0..k).map(|c| {
+                    let var = (0..d).fold(0., |a, p| a + scatters[c][(p, p)]) / nk[c] / d as f64;
+                    DMat::from_fn(d, d, |p, q| if p == q { var + reg } else { 0. })
+                }).collect()
+            }
+        };
+    }
+
+    /// log(weight) + log(density) of each record (row) and component (column)
+    fn weighted_log_prob(&self, data: &DMat<f64>) -> DMat<f64> {
+        let d = data.ncols();
+        let k = self.ncomponents;
+        assert!(self.covariances.len() == k, "GaussianMixture is not fitted");
+
+        let mut log_prob = DMat::from_elem(data.nrows(), k, 0.);
+        for c in 0..k {
+            let chol = match cholesky(&self.covariances[c]) {
+                Some(l) => l,
+                None => panic!("Covariance matrix is not positive definite, increase reg_covar")
+            };
+            let log_det = (0..d).fold(0., |a, p| a + chol[(p, p)].ln()) * 2.;
+            let mean = self.means.row_slice(c, 0, d);
+
+            for i in 0..data.nrows() {
+                let diff = data.row_slice(i, 0, d) - mean.clone();
+                let z = forward_substitution(&chol, &diff);
+                let mahalanobis = z.at.iter().fold(0., |a, v| a + v * v);
+                log_prob[(i, c)] = self.weights[c].ln()
+                    - 0.5 * (d as f64 * (2. * f64::consts::PI).ln() + log_det + mahalanobis);
+            }
+        }
+        return log_prob;
+    }
+
+    /// log-likelihood of each record
+    pub fn score_samples(&self, data: &DMat<f64>) -> DVec<f64> {
+        let log_prob = self.weighted_log_prob(data);
+        return DVec::from_fn(data.nrows(), |i| logsumexp(&log_prob, i));
+    }
+
+    /// total log-likelihood of the data
+    pub fn score(&self, data: &DMat<f64>) -> f64 {
+        return self.score_samples(data).at.iter().fold(0., |a, v| a + v);
+    }
+
+    /// probability that each record (row) belongs to each component (column)
+    pub fn predict_proba(&self, data: &DMat<f64>) -> DMat<f64> {
+        let (resp, _) = self.e_step(data);
+        return resp;
+    }
+
+    /// component with the highest probability
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<usize> {
+        let log_prob = self.weighted_log_prob(data);
+        return DVec::from_fn(data.nrows(), |i| {
+            let mut tmp_c = 0;
+            for c in 1..self.ncomponents {
+                if log_prob[(i, c)] > log_prob[(i, tmp_c)] {
+                    tmp_c = c;
+                }
+            }
+            tmp_c
+        });
+    }
+
+    /// number of free parameters
+    pub fn nparameters(&self) -> usize {
+        let k = self.ncomponents;
+        let d = self.means.ncols();
+        let cov_params = match self.covariance_type {
+            CovarianceType::Full => k * d * (d + 1) / 2,
+            CovarianceType::Diag => k * d,
+            CovarianceType::Tied => d * (d + 1) / 2,
+            CovarianceType::Spherical => k
+        };
+        return cov_params + k * d + k - 1;
+    }
+
+    /// Bayesian information criterion, lower is better
+    pub fn bic(&self, data: &DMat<f64>) -> f64 {
+        return -2. * self.score(data) + self.nparameters() as f64 * (data.nrows() as f64).ln();
+    }
+
+    /// Akaike information criterion, lower is better
+    pub fn aic(&self, data: &DMat<f64>) -> f64 {
+        return -2. * self.score(data) + 2. * self.nparameters() as f64;
+    }
+}
+
+/// log(sum(exp(x))) of the row
+fn logsumexp(x: &DMat<f64>, row: usize) -> f64 {
+    let max = (0..x.ncols()).fold(f64::NEG_INFINITY, |a, c| a.max(x[(row, c)]));
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    return max + (0..x.ncols()).fold(0., |a, c| a + (x[(row, c)] - max).exp()).ln();
+}
+
+/// lower triangular L where L * L^T = m, None if m is not positive definite
+fn cholesky(m: &DMat<f64>) -> Option<DMat<f64>> {
+    let d = m.nrows();
+    let mut l = DMat::from_elem(d, d, 0.);
+    for j in 0..d {
+        let s = (0..j).fold(m[(j, j)], |a, p| a - l[(j, p)] * l[(j, p)]);
+        if s <= 0. {
+            return None;
+        }
+        l[(j, j)] = s.sqrt();
+        for i in (j + 1)..d {
+            let s = (0..j).fold(m[(i, j)], |a, p| a - l[(i, p)] * l[(j, p)]);
+            l[(i, j)] = s / l[(j, j)];
+        }
+    }
+    return Some(l);
+}
+
+/// solve L * z = b for lower triangular L
+fn forward_substitution(l: &DMat<f64>, b: &DVec<f64>) -> DVec<f64> {
+    let d = b.len();
+    let mut z = DVec::from_elem(d, 0.);
+    for i in 0..d {
+        let s = (0..i).fold(b[i], |a, p| a - l[(i, p)] * z[p]);
+        z[i] = s / l[(i, i)];
+    }
+    return z;
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMat;
+    use super::{GaussianMixture, CovarianceType, cholesky};
+
+    fn get_data() -> DMat<f64> {
+        // two blobs of 20 records on a grid
+        return DMat::from_fn(40, 2, |i, j| {
+            let v = if j == 0 { (i % 5) as f64 } else { ((i / 5) % 4) as f64 };
+            if i < 20 { v } else { v + 20. }
+        });
+    }
+
+    #[test]
+    fn test_cholesky() {
+        let m: DMat<f64> = DMat::from_row_vec(2, 2, &vec![4., 2., 2., 5.]);
+        let l = cholesky(&m).unwrap();
+        assert_eq!(l, DMat::from_row_vec(2, 2, &vec![2., 0., 1., 2.]));
+
+        let m: DMat<f64> = DMat::from_row_vec(2, 2, &vec![1., 2., 2., 1.]);
+        assert!(cholesky(&m).is_none());
+    }
+
+    #[test]
+    fn test_gmm() {
+        let data = get_data();
+
+        for cov in vec![CovarianceType::Full, CovarianceType::Diag,
+                        CovarianceType::Tied, CovarianceType::Spherical] {
+            let mut gmm = GaussianMixture::new(2, 100);
+            gmm.covariance_type = cov;
+            gmm.seed = Some(1);
+            gmm.fit(&data);
+            assert!(gmm.converged);
+
+            let labels = gmm.predict(&data);
+            for i in 0..40 {
+                assert_eq!(labels[i] == labels[0], i < 20);
+            }
+            assert!((gmm.weights[0] - 0.5).abs() < 1e-6);
+
+            let proba = gmm.predict_proba(&data);
+            for i in 0..40 {
+                assert!((proba[(i, 0)] + proba[(i, 1)] - 1.).abs() < 1e-10);
+                assert!(proba[(i, labels[i])] > 0.99);
+            }
+            assert!((gmm.score(&data) - gmm.log_likelihood).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_gmm_bic() {
+        let data = get_data();
+
+        let mut gmm1 = GaussianMixture::new(1, 100);
+        gmm1.seed = Some(1);
+        gmm1.fit(&data);
+        // means 2 + weights 0 + covariances 3
+        assert_eq!(gmm1.nparameters(), 5);
+
+        let mut gmm2 = GaussianMixture::new(2, 100);
+        gmm2.seed = Some(1);
+        gmm2.fit(&data);
+        assert_eq!(gmm2.nparameters(), 11);
+
+        assert!(gmm2.bic(&data) < gmm1.bic(&data));
+        assert!(gmm2.aic(&data) < gmm1.aic(&data));
+        assert_eq!(gmm2.aic(&data), -2. * gmm2.log_likelihood + 22.);
+    }
+}
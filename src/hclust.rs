@@ -3,23 +3,31 @@ extern crate nalgebra;
 extern crate num;
 extern crate rand;
 
-use nalgebra::{DMat, RowSlice, Iterable};
-use num::Float;
+use nalgebra::{DMat, DVec, RowSlice};
 use std::f64;
 use std::ops::Index;
 
-use super::mathfunc::{euc_dist};
+use super::mathfunc::distance::{Metric, Euclidean, Correlation};
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ClusterDistance {
     Single,                 // Minimum clustering
     Complete,               // Maximum clustering
     Average,                // Mean clustering
+    Weighted,               // McQuitty's method
+    Ward,                   // Ward's minimum variance method (ward.D2 in R)
+    Centroid,               // distance between centroids
+    Median,                 // distance between centroids weighted equally on merge
 }
 
 pub struct HClust {
-    dist_mat: DMat<f64>,        // distance matrix
+    dist_mat: DMat<f64>,        // distances between records in the lower triangle, the upper
+                                // triangle is used for distances between clusters on merge
     method: ClusterDistance,    // linkage criteria
-    clusters: Vec<Cluster>
+    nrecords: usize,
+    clusters: Vec<Cluster>,
+    cluster_dists: Vec<Vec<f64>>,               // distances between clusters, updated on merge
+    merges: Vec<(usize, usize, f64, usize)>    // merged cluster ids, distance and size
 }
 
 impl HClust {
@@ -29,23 +37,396 @@ impl HClust {
             // dummy
             dist_mat: DMat::from_elem(1, 1, 1.),
             method: method,
-            clusters: vec![]
+            nrecords: 0,
+            clusters: vec![],
+            cluster_dists: vec![],
+            merges: vec![]
         }
     }
 
+    /// fit using Euclid distance
     pub fn fit(&mut self, data: &DMat<f64>) {
-        self.dist_mat = self.get_dist_matrix(&data);
+        self.fit_with(data, Euclidean);
+    }
+
+    /// fit using the specified metric
+    ///
+    /// Ward, Centroid and Median assume Euclid distance.
+    pub fn fit_with<M: Metric>(&mut self, data: &DMat<f64>, metric: M) {
+        self.dist_mat = self.get_dist_matrix(&data, &metric);
+        self.fit_records(data.nrows());
+    }
+
+    /// fit from a dissimilarity matrix (same as hclust(as.dist(m)) in R)
+    ///
+    /// A square matrix is read as distances between all pairs, so 1 x 1 matrix is a single
+    /// record. It must be symmetric and its diagonal must be 0. Otherwise a matrix with
+    /// 1 row (or 1 column) of n * (n - 1) / 2 values is read as a condensed matrix, which has
+    /// distances in the order of (0, 1), (0, 2), ..., (0, n - 1), (1, 2), ... as R and SciPy.
+    pub fn fit_dist(&mut self, dist: &DMat<f64>) {
+        assert!(dist.nrows() > 0 && dist.ncols() > 0, "Dissimilarity matrix must not be empty");
+        let mut values: Vec<f64> = vec![];
+        if dist.nrows() == dist.ncols() {
+            for i in 0..dist.nrows() {
+                assert!(dist[(i, i)] == 0., "Diagonal of dissimilarity matrix must be 0");
+                for j in (i + 1)..dist.ncols() {
+                    let (upper, lower) = (dist[(i, j)], dist[(j, i)]);
+                    assert!(upper.is_finite() && lower.is_finite() && upper >= 0. && lower >= 0.,
+                            "Dissimilarities must be finite and non-negative");
+                    assert!((upper - lower).abs() <= 1e-10 * upper.abs().max(lower.abs()).max(1.),
+                            "Dissimilarity matrix must be symmetric");
+                    values.push(lower);
+                }
+            }
+        } else {
+            assert!(dist.nrows() == 1 || dist.ncols() == 1,
+                    "Dissimilarity matrix must be square or condensed");
+            for &v in dist.as_vec() {
+                assert!(v.is_finite() && v >= 0., "Dissimilarities must be finite and non-negative");
+                values.push(v);
+            }
+        }
 
+        // number of records from the number of distances, n * (n - 1) / 2
+        let n = ((1. + (1. + 8. * values.len() as f64).sqrt()) / 2.).round() as usize;
+        assert!(n * (n - 1) / 2 == values.len(), "Invalid length of condensed dissimilarity matrix");
+
+        let mut dist_mat = DMat::from_elem(n, n, 0.);
+        let mut k = 0;
+        for j in 0..n {
+            for i in (j + 1)..n {
+                dist_mat[(i, j)] = values[k];
+                k += 1;
+            }
+        }
+        self.dist_mat = dist_mat;
+        self.fit_records(n);
+    }
+
+    /// build the tree from the distance matrix of nrecords records
+    fn fit_records(&mut self, nrecords: usize) {
         // initialize clusters
-        for i in 0..data.nrows() {
-            let c = Cluster::from_nodes(vec![i]);
+        self.nrecords = nrecords;
+        self.clusters.clear();
+        self.merges.clear();
+        for i in 0..nrecords {
+            let c = Cluster::from_nodes(i, vec![i]);
             self.clusters.push(c);
         }
+
+        match self.method {
+            ClusterDistance::Single => {
+                let merges = self.slink();
+                self.build_tree(merges);
+            },
+            ClusterDistance::Centroid | ClusterDistance::Median => {
+                // nearest-neighbor chain can't be used because distances may decrease on merge
+                self.fit_pairwise();
+            },
+            _ => {
+                let merges = self.nn_chain();
+                self.build_tree(merges);
+            }
+        }
+    }
+
+    /// root of the dendrogram
+    pub fn root(&self) -> &Cluster {
+        assert!(self.clusters.len() == 1, "HClust is not fitted");
+        return &self.clusters[0];
+    }
+
+    /// linkage matrix (same as SciPy)
+    ///
+    /// i-th row represents i-th merge, columns are ids of merged clusters, distance
+    /// and the number of records in the new cluster. Records have ids 0..n and
+    /// the cluster created by i-th merge has id n + i.
+    pub fn linkage(&self) -> DMat<f64> {
+        let mut values: Vec<f64> = vec![];
+        for &(left, right, distance, size) in &self.merges {
+            values.push(left as f64);
+            values.push(right as f64);
+            values.push(distance);
+            values.push(size as f64);
+        }
+        return DMat::from_row_vec(self.merges.len(), 4, &values);
+    }
+
+    /// records in the order of dendrogram leaves
+    pub fn order(&self) -> Vec<usize> {
+        return self.root().nodes.clone();
+    }
+
+    /// cut the tree into k clusters and return the cluster of each record
+    ///
+    /// Clusters are numbered from 0 in the order of their first record (same as R).
+    pub fn cutree(&self, k: usize) -> DVec<usize> {
+        assert!(1 <= k && k <= self.nrecords, "k must be between 1 and the number of records");
+        return self.cut_merges(self.nrecords - k);
+    }
+
+    /// cut the tree at the height and return the cluster of each record
+    ///
+    /// Heights of merges must be increasing. Centroid and Median may merge at a lower
+    /// height than the previous merge, cut such trees by the number of clusters (same as R).
+    pub fn cutree_height(&self, height: f64) -> DVec<usize> {
+        for w in self.merges.windows(2) {
+            assert!(w[0].2 <= w[1].2, "Heights of merges must be increasing to cut by height");
+        }
+        let nmerges = self.merges.iter().take_while(|m| m.2 <= height).count();
+        return self.cut_merges(nmerges);
+    }
+
+    /// cophenetic distances, heights of the merges where each pair of records is joined
+    pub fn cophenetic(&self) -> DMat<f64> {
+        let n = self.nrecords;
+        let mut coph = DMat::from_elem(n, n, 0.);
+        for c in self.root().walk() {
+            if c.is_leaf() {
+                continue;
+            }
+            for &i in &c.children[0].nodes {
+                for &j in &c.children[1].nodes {
+                    coph[(i, j)] = c.distance;
+                    coph[(j, i)] = c.distance;
+                }
+            }
+        }
+        return coph;
+    }
+
+    /// correlation coefficient between cophenetic distances and the original distances
+    ///
+    /// NaN when either distances have no variance, e.g. 2 records or equidistant records
+    /// (R's cor gives NA).
+    pub fn cophenetic_correlation(&self) -> f64 {
+        let n = self.nrecords;
+        let coph = self.cophenetic();
+        let mut original: Vec<f64> = vec![];
+        let mut cophenetic: Vec<f64> = vec![];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                original.push(self.get_node_dist(i, j));
+                cophenetic.push(coph[(i, j)]);
+            }
+        }
+        let constant = |v: &Vec<f64>| v.iter().all(|&d| d == v[0]);
+        if original.len() == 0 || constant(&original) || constant(&cophenetic) {
+            return f64::NAN;
+        }
+        let original = DVec::from_slice(original.len(), &original);
+        let cophenetic = DVec::from_slice(cophenetic.len(), &cophenetic);
+        return 1. - Correlation.distance(&original, &cophenetic);
+    }
+
+    /// dendrogram in Newick format
+    ///
+    /// Branch lengths are differences of merge distances, negative lengths caused by
+    /// inversions of Centroid and Median are written as 0. Leaves are named by labels
+    /// (e.g. the first column of CSV) or record numbers when labels are not specified.
+    pub fn to_newick(&self, labels: Option<&[String]>) -> String {
+        self.check_labels(labels);
+        let mut result = String::new();
+        self.root().write_newick(labels, &mut result);
+        result.push(';');
+        return result;
+    }
+
+    /// dendrogram as nested JSON objects
+    ///
+    /// Each cluster has "id" and "distance", leaves have "name" and other clusters
+    /// have "children". Infinite or NaN distances are written as null.
+    pub fn to_json(&self, labels: Option<&[String]>) -> String {
+        self.check_labels(labels);
+        let mut result = String::new();
+        self.root().write_json(labels, &mut result);
+        return result;
+    }
+
+    fn check_labels(&self, labels: Option<&[String]>) {
+        if let Some(l) = labels {
+            assert!(l.len() == self.nrecords, "Number of labels must be the number of records");
+        }
+    }
+
+    /// labels of records after applying the first nmerges merges
+    fn cut_merges(&self, nmerges: usize) -> DVec<usize> {
+        // representative record of each cluster id
+        let mut parents: Vec<usize> = (0..self.nrecords).collect();
+        let mut representatives: Vec<usize> = (0..self.nrecords).collect();
+
+        for &(left, right, _, _) in self.merges.iter().take(nmerges) {
+            let (l, r) = (representatives[left], representatives[right]);
+            parents[r] = l;
+            representatives.push(l);
+        }
+
+        // find root of each record
+        let find = |mut i: usize| {
+            while parents[i] != i {
+                i = parents[i];
+            }
+            i
+        };
+
+        let mut labels: Vec<usize> = vec![];
+        let mut roots: Vec<usize> = vec![];
+        for i in 0..self.nrecords {
+            let root = find(i);
+            let label = match roots.iter().position(|&r| r == root) {
+                Some(l) => l,
+                None => {
+                    roots.push(root);
+                    roots.len() - 1
+                }
+            };
+            labels.push(label);
+        }
+        return DVec::from_slice(self.nrecords, &labels);
+    }
+
+    /// merge the closest pair of clusters one by one, O(n^3) time
+    fn fit_pairwise(&mut self) {
+        self.cluster_dists = self.init_cluster_dists();
         while self.clusters.len() > 1 {
             self.fit_step();
         }
     }
 
+    /// distances between records used by Lance-Williams formula
+    fn init_cluster_dists(&self) -> Vec<Vec<f64>> {
+        return (0..self.nrecords).map(|i| {
+            (0..self.nrecords).map(|j| {
+                if i == j { 0. } else { self.from_node_dist(self.get_node_dist(i, j)) }
+            }).collect()
+        }).collect();
+    }
+
+    /// SLINK algorithm for single linkage, O(n^2) time
+    ///
+    /// Uses O(n) memory in addition to the distance matrix.
+    ///
+    /// Returns merges as pairs of records belonging to the merged clusters and the distance.
+    fn slink(&self) -> Vec<(usize, usize, f64)> {
+        let n = self.nrecords;
+        // pointer representation, pi[i] is the last record merged with i at distance lambda[i]
+        let mut pi: Vec<usize> = vec![0; n];
+        let mut lambda: Vec<f64> = vec![f64::INFINITY; n];
+        let mut m: Vec<f64> = vec![0.; n];
+
+        for i in 0..n {
+            pi[i] = i;
+            lambda[i] = f64::INFINITY;
+            for j in 0..i {
+                m[j] = self.get_node_dist(i, j);
+            }
+            for j in 0..i {
+                if lambda[j] >= m[j] {
+                    m[pi[j]] = m[pi[j]].min(lambda[j]);
+                    lambda[j] = m[j];
+                    pi[j] = i;
+                } else {
+                    m[pi[j]] = m[pi[j]].min(m[j]);
+                }
+            }
+            for j in 0..i {
+                if lambda[j] >= lambda[pi[j]] {
+                    pi[j] = i;
+                }
+            }
+        }
+        // only the last record points itself, others are merged even at infinite distance
+        return (0..n).filter(|&i| pi[i] != i)
+                     .map(|i| (i, pi[i], lambda[i]))
+                     .collect();
+    }
+
+    /// nearest-neighbor chain algorithm for reducible linkages, O(n^2) time
+    ///
+    /// Follows nearest neighbors until reciprocal nearest neighbors are found and merges them.
+    /// Distances between clusters are updated in the upper triangle of the distance matrix.
+    /// Returns merges as pairs of records belonging to the merged clusters and the distance.
+    fn nn_chain(&mut self) -> Vec<(usize, usize, f64)> {
+        let n = self.nrecords;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                self.dist_mat[(i, j)] = self.from_node_dist(self.dist_mat[(j, i)]);
+            }
+        }
+        // merged cluster takes the slot of the larger record, the other slot becomes inactive
+        let mut active: Vec<bool> = vec![true; n];
+        let mut sizes: Vec<usize> = vec![1; n];
+        let mut chain: Vec<usize> = vec![];
+        let mut merges: Vec<(usize, usize, f64)> = vec![];
+
+        while merges.len() + 1 < n {
+            if chain.len() == 0 {
+                chain.push(active.iter().position(|&a| a).unwrap());
+            }
+
+            let mut a;
+            let mut b;
+            let mut current_dist;
+            loop {
+                a = chain[chain.len() - 1];
+                // prefer the previous element of the chain on ties to avoid cycles
+                if chain.len() >= 2 {
+                    b = chain[chain.len() - 2];
+                    current_dist = self.get_cluster_dist(a, b);
+                } else {
+                    // any other active cluster, so that b != a even if all distances are inf
+                    b = (0..n).find(|&x| active[x] && x != a).unwrap();
+                    current_dist = self.get_cluster_dist(a, b);
+                }
+                for x in 0..n {
+                    if active[x] && x != a && self.get_cluster_dist(a, x) < current_dist {
+                        current_dist = self.get_cluster_dist(a, x);
+                        b = x;
+                    }
+                }
+                if chain.len() >= 2 && b == chain[chain.len() - 2] {
+                    break;
+                }
+                chain.push(b);
+            }
+            chain.pop();
+            chain.pop();
+
+            let (i, j) = if a < b { (a, b) } else { (b, a) };
+            for k in 0..n {
+                if active[k] && k != i && k != j {
+                    let d = self.lance_williams(self.get_cluster_dist(i, k),
+                                                self.get_cluster_dist(j, k), current_dist,
+                                                sizes[i], sizes[j], sizes[k]);
+                    self.set_cluster_dist(j, k, d);
+                }
+            }
+            active[i] = false;
+            sizes[j] += sizes[i];
+            merges.push((i, j, self.to_height(current_dist)));
+        }
+        return merges;
+    }
+
+    /// build the dendrogram from merges in arbitrary order
+    ///
+    /// Merges are applied in ascending order of distance, so the tree is the same as
+    /// the one created by merging the closest clusters one by one.
+    fn build_tree(&mut self, mut merges: Vec<(usize, usize, f64)>) {
+        // stable sort keeps the order of merges with the same distance
+        merges.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+        for (a, b, distance) in merges {
+            let pos_a = self.clusters.iter().position(|c| c.nodes.contains(&a)).unwrap();
+            let pos_b = self.clusters.iter().position(|c| c.nodes.contains(&b)).unwrap();
+            let (tmp_i, tmp_j) = if pos_a < pos_b { (pos_a, pos_b) } else { (pos_b, pos_a) };
+
+            // same as fit_step, clusters are ordered by ids
+            let left = self.clusters.remove(tmp_j);
+            let right = self.clusters.remove(tmp_i);
+            self.merge(left, right, distance);
+        }
+    }
+
     /// merge closest clusters
     fn fit_step(&mut self) {
         let mut tmp_i = 0;
@@ -54,7 +435,7 @@ impl HClust {
 
         for i in 0..self.clusters.len() {
             for j in (i + 1)..self.clusters.len() {
-                let d = self.get_cluster_dist(&self.clusters[i], &self.clusters[j]);
+                let d = self.cluster_dists[i][j];
                 if d < current_dist {
                     current_dist = d;
                     tmp_i = i;
@@ -63,69 +444,129 @@ impl HClust {
             }
         }
 
-        let mut new_clusters: Vec<Cluster> = vec![];
-        for (i, n) in self.clusters.iter().enumerate() {
-            if i != tmp_i && i != tmp_j {
-                let n2 = Cluster::from_nodes(n.nodes.clone());
-                new_clusters.push(n2);
+        // distances from other clusters to the merged cluster
+        let ni = self.clusters[tmp_j].nodes.len();
+        let nj = self.clusters[tmp_i].nodes.len();
+        let mut new_dists: Vec<f64> = vec![];
+        for k in 0..self.clusters.len() {
+            if k != tmp_i && k != tmp_j {
+                new_dists.push(self.lance_williams(self.cluster_dists[tmp_j][k],
+                                                   self.cluster_dists[tmp_i][k],
+                                                   current_dist, ni, nj,
+                                                   self.clusters[k].nodes.len()));
             }
         }
-        // take elements from Vec and move ownership to the new instance
-        let new = Cluster::from_clusters(self.clusters.swap_remove(tmp_j),
-                                         self.clusters.swap_remove(tmp_i),
-                                         current_dist);
+        self.cluster_dists.remove(tmp_j);
+        self.cluster_dists.remove(tmp_i);
+        for (row, d) in self.cluster_dists.iter_mut().zip(new_dists.iter()) {
+            row.remove(tmp_j);
+            row.remove(tmp_i);
+            row.push(*d);
+        }
+        new_dists.push(0.);
+        self.cluster_dists.push(new_dists);
 
-        new_clusters.push(new);
-        self.clusters = new_clusters;
+        // take elements from Vec and move ownership to the new instance
+        // (tmp_i < tmp_j, remove tmp_j first to keep tmp_i)
+        let left = self.clusters.remove(tmp_j);
+        let right = self.clusters.remove(tmp_i);
+        let distance = self.to_height(current_dist);
+        self.merge(left, right, distance);
     }
 
-    /// get distance between clusters
-    fn get_cluster_dist(&self, c1: &Cluster, c2: &Cluster) -> f64 {
-        let mut dists: Vec<f64> = vec![];
-        for i in &c1.nodes {
-            for j in &c2.nodes {
-                dists.push(self.get_node_dist(*i, *j));
-            }
-        }
-        return self.fold_dist_vec(dists);
+    /// merge 2 clusters and record it
+    fn merge(&mut self, left: Cluster, right: Cluster, distance: f64) {
+        let id = self.nrecords + self.merges.len();
+
+        self.merges.push((left.id.min(right.id), left.id.max(right.id), distance,
+                          left.nodes.len() + right.nodes.len()));
+        let new = Cluster::from_clusters(id, left, right, distance);
+        self.clusters.push(new);
     }
 
-    /// get cluster distance from node distances
-    fn fold_dist_vec(&self, dists: Vec<f64>) -> f64 {
+    /// Lance-Williams formula, distance between cluster k and the cluster merging i and j
+    fn lance_williams(&self, d_ki: f64, d_kj: f64, d_ij: f64,
+                      ni: usize, nj: usize, nk: usize) -> f64 {
+        let (ni, nj, nk) = (ni as f64, nj as f64, nk as f64);
         match self.method {
             ClusterDistance::Single => {
-                return dists.iter().fold(f64::MAX, |a, b| a.min(*b));
+                return d_ki.min(d_kj);
             },
             ClusterDistance::Complete => {
-                return dists.iter().fold(f64::MIN, |a, b| a.max(*b));
+                return d_ki.max(d_kj);
             },
             ClusterDistance::Average => {
-                return dists.iter().fold(0., |a, b| a + b) / (dists.len() as f64);
+                return (ni * d_ki + nj * d_kj) / (ni + nj);
+            },
+            ClusterDistance::Weighted => {
+                return (d_ki + d_kj) / 2.;
+            },
+            ClusterDistance::Ward => {
+                return ((ni + nk) * d_ki + (nj + nk) * d_kj - nk * d_ij) / (ni + nj + nk);
+            },
+            ClusterDistance::Centroid => {
+                return (ni * d_ki + nj * d_kj) / (ni + nj) - ni * nj * d_ij / ((ni + nj) * (ni + nj));
+            },
+            ClusterDistance::Median => {
+                return d_ki / 2. + d_kj / 2. - d_ij / 4.;
             },
         }
     }
 
+    /// Ward, Centroid and Median are updated on squared distances
+    fn is_squared(&self) -> bool {
+        match self.method {
+            ClusterDistance::Ward | ClusterDistance::Centroid | ClusterDistance::Median => true,
+            _ => false
+        }
+    }
+
+    /// convert distance between records to the value used by Lance-Williams formula
+    fn from_node_dist(&self, d: f64) -> f64 {
+        return if self.is_squared() { d * d } else { d };
+    }
+
+    /// convert value used by Lance-Williams formula to the height of the merge
+    fn to_height(&self, d: f64) -> f64 {
+        // squared distances can be slightly negative by rounding errors
+        return if self.is_squared() { d.max(0.).sqrt() } else { d };
+    }
+
     /// get distance matrix
-    fn get_dist_matrix(&self, data: &DMat<f64>) -> DMat<f64> {
-        // column corresponding to 0 to nthnodes
-        // row corresponding to 1 to nth nodes
-        return DMat::from_fn(data.nrows() - 1, data.nrows() - 1,
-                             |i, j| if i >= j {
-                                euc_dist(&data.row_slice(i + 1, 0, data.ncols()),
-                                         &data.row_slice(j, 0, data.ncols()))}
+    fn get_dist_matrix<M: Metric>(&self, data: &DMat<f64>, metric: &M) -> DMat<f64> {
+        // lower triangle only
+        return DMat::from_fn(data.nrows(), data.nrows(),
+                             |i, j| if i > j {
+                                metric.distance(&data.row_slice(i, 0, data.ncols()),
+                                                &data.row_slice(j, 0, data.ncols()))}
                                 else { 0. });
     }
 
     /// get distance between nodes using distance matrix
     fn get_node_dist(&self, i: usize, j: usize) -> f64 {
         match i > j {
-            true => *self.dist_mat.index((i - 1, j)),
-            false => *self.dist_mat.index((j - 1, i))
+            true => *self.dist_mat.index((i, j)),
+            false => *self.dist_mat.index((j, i))
+        }
+    }
+
+    /// distance between clusters in the slots i and j, updated by nn_chain
+    fn get_cluster_dist(&self, i: usize, j: usize) -> f64 {
+        return if i < j { self.dist_mat[(i, j)] } else { self.dist_mat[(j, i)] };
+    }
+
+    fn set_cluster_dist(&mut self, i: usize, j: usize, d: f64) {
+        if i < j {
+            self.dist_mat[(i, j)] = d;
+        } else {
+            self.dist_mat[(j, i)] = d;
         }
     }
 }
 
-struct Cluster {
+/// Node of dendrogram
+pub struct Cluster {
+    id: usize,
     nodes: Vec<usize>,
     distance: f64,
     children: Vec<Cluster>
@@ -133,8 +574,9 @@ struct Cluster {
 
 impl Cluster {
 
-    fn from_nodes(nodes: Vec<usize>) -> Cluster {
+    fn from_nodes(id: usize, nodes: Vec<usize>) -> Cluster {
         Cluster {
+            id: id,
             nodes: nodes,
             distance: 0.,
             children: vec![]
@@ -142,7 +584,7 @@ impl Cluster {
     }
 
     /// create a cluster merging 2 clusters
-    fn from_clusters(left: Cluster, right: Cluster, distance: f64) -> Cluster {
+    fn from_clusters(id: usize, left: Cluster, right: Cluster, distance: f64) -> Cluster {
         let mut nodes = vec![];
         for i in &left.nodes {
             nodes.push(*i);
@@ -151,18 +593,144 @@ impl Cluster {
             nodes.push(*j);
         }
         Cluster {
+            id: id,
             nodes: nodes,
             distance: distance,
             children: vec![left, right]
         }
     }
+
+    /// id used in linkage matrix, record number for leaves
+    pub fn id(&self) -> usize {
+        return self.id;
+    }
+
+    /// records belonging to the cluster, in the order of dendrogram leaves
+    pub fn nodes(&self) -> &[usize] {
+        return &self.nodes;
+    }
+
+    /// distance between children when merged, 0 for leaves
+    pub fn distance(&self) -> f64 {
+        return self.distance;
+    }
+
+    /// left and right children, empty for leaves
+    pub fn children(&self) -> &[Cluster] {
+        return &self.children;
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        return self.children.len() == 0;
+    }
+
+    /// all clusters in the subtree in pre-order (self, left subtree, right subtree)
+    pub fn walk(&self) -> Vec<&Cluster> {
+        let mut result = vec![self];
+        for child in &self.children {
+            result.extend(child.walk());
+        }
+        return result;
+    }
+
+    fn name(&self, labels: Option<&[String]>) -> String {
+        return match labels {
+            Some(l) => l[self.id].clone(),
+            None => self.id.to_string()
+        };
+    }
+
+    /// subtree in Newick format without branch length of itself
+    fn write_newick(&self, labels: Option<&[String]>, result: &mut String) {
+        if self.is_leaf() {
+            result.push_str(&newick_label(&self.name(labels)));
+            return;
+        }
+        result.push('(');
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                result.push(',');
+            }
+            child.write_newick(labels, result);
+            // inversions of Centroid and Median give negative lengths
+            result.push_str(&format!(":{}", (self.distance - child.distance).max(0.)));
+        }
+        result.push(')');
+    }
+
+    fn write_json(&self, labels: Option<&[String]>, result: &mut String) {
+        result.push_str(&format!("{{\"id\":{},", self.id));
+        if self.is_leaf() {
+            result.push_str(&format!("\"name\":{},", json_string(&self.name(labels))));
+        }
+        if self.distance.is_finite() {
+            result.push_str(&format!("\"distance\":{}", self.distance));
+        } else {
+            result.push_str("\"distance\":null");
+        }
+        if !self.is_leaf() {
+            result.push_str(",\"children\":[");
+            for (i, child) in self.children.iter().enumerate() {
+                if i > 0 {
+                    result.push(',');
+                }
+                child.write_json(labels, result);
+            }
+            result.push(']');
+        }
+        result.push('}');
+    }
+}
+
+/// quote the label if it contains characters having special meanings in Newick
+fn newick_label(label: &str) -> String {
+    let special = |c: char| c.is_whitespace() || "()[]':;,".contains(c);
+    if label.chars().any(special) {
+        return format!("'{}'", label.replace("'", "''"));
+    }
+    return label.to_string();
+}
+
+/// JSON string literal
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    return result;
 }
 
 #[cfg(test)]
 mod tests {
     extern crate csv;
+    use nalgebra::{DVec, DMat};
+    use std::f64;
     use super::{HClust, ClusterDistance};
-    use super::super::io::read_csv_f64;
+    use super::super::mathfunc::distance::{Metric, Manhattan, Euclidean, pairwise_distances};
+    use super::super::io::{read_csv_f64, read_csv_strings};
+
+    const SAMPLE: &'static str = "名前,算数,理科,国語,英語,社会
+    田中,89,90,67,46,50
+    佐藤,57,70,80,85,90
+    鈴木,80,90,35,40,50
+    本田,40,60,50,45,55
+    川端,78,85,45,55,60
+    吉野,55,65,80,75,85
+    斉藤,90,85,88,92,95";
+
+    fn sample_data() -> DMat<f64> {
+        let mut reader = csv::Reader::from_string(SAMPLE).has_headers(true);
+        return read_csv_f64(&mut reader);
+    }
 
     #[test]
     fn test_hclust() {
@@ -193,4 +761,406 @@ mod tests {
         hclust.fit(&dx);
         assert_eq!(hclust.clusters[0].distance, 69.92295649225116);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hclust_algorithms() {
+        let dx = sample_data();
+
+        let square = pairwise_distances(&dx, &dx, &Euclidean);
+
+        // SLINK and nearest-neighbor chain give the same trees as merging the closest pair
+        let methods = vec![ClusterDistance::Single, ClusterDistance::Complete,
+                           ClusterDistance::Average, ClusterDistance::Weighted,
+                           ClusterDistance::Ward];
+        for method in methods {
+            let (heights, labels) = naive_hclust(&square, method);
+
+            let mut from_data = HClust::new(method);
+            from_data.fit(&dx);
+            let mut from_dist = HClust::new(method);
+            from_dist.fit_dist(&square);
+
+            for hclust in &[from_data, from_dist] {
+                let linkage = hclust.linkage();
+                for (i, &height) in heights.iter().enumerate() {
+                    assert!((linkage[(i, 2)] - height).abs() < 1e-10);
+                    assert_eq!(hclust.cutree(7 - i - 1).at, labels[i]);
+                }
+            }
+        }
+    }
+
+    /// merge the closest pair one by one, updating distances by Lance-Williams formula,
+    /// and return heights of merges and clusters of records after each merge
+    fn naive_hclust(dist: &DMat<f64>, method: ClusterDistance) -> (Vec<f64>, Vec<Vec<usize>>) {
+        let n = dist.nrows();
+        let mut d = dist.clone();
+        let mut sizes = vec![1.; n];
+        let mut active: Vec<usize> = (0..n).collect();
+        let mut members: Vec<usize> = (0..n).collect();
+
+        let mut heights = vec![];
+        let mut labels = vec![];
+        while active.len() > 1 {
+            let (mut i, mut j, mut h) = (0, 0, f64::INFINITY);
+            for (a, &p) in active.iter().enumerate() {
+                for &q in &active[(a + 1)..] {
+                    if d[(p, q)] < h {
+                        i = p;
+                        j = q;
+                        h = d[(p, q)];
+                    }
+                }
+            }
+            for &k in &active {
+                if k == i || k == j {
+                    continue;
+                }
+                let (ni, nj, nk) = (sizes[i], sizes[j], sizes[k]);
+                let (dik, djk) = (d[(i, k)], d[(j, k)]);
+                let updated = match method {
+                    ClusterDistance::Single => dik.min(djk),
+                    ClusterDistance::Complete => dik.max(djk),
+                    ClusterDistance::Average => (ni * dik + nj * djk) / (ni + nj),
+                    ClusterDistance::Weighted => (dik + djk) / 2.,
+                    ClusterDistance::Ward => (((ni + nk) * dik * dik + (nj + nk) * djk * djk
+                                               - nk * h * h) / (ni + nj + nk)).sqrt(),
+                    _ => panic!("Not supported")
+                };
+                d[(i, k)] = updated;
+                d[(k, i)] = updated;
+            }
+            sizes[i] += sizes[j];
+            active.retain(|&k| k != j);
+            for m in members.iter_mut() {
+                if *m == j {
+                    *m = i;
+                }
+            }
+
+            // number clusters in the order of their first record
+            let mut roots: Vec<usize> = vec![];
+            for &m in &members {
+                if !roots.contains(&m) {
+                    roots.push(m);
+                }
+            }
+            heights.push(h);
+            labels.push(members.iter().map(|m| roots.iter().position(|r| r == m).unwrap())
+                               .collect());
+        }
+        return (heights, labels);
+    }
+
+    #[test]
+    fn test_hclust_metric() {
+        let dx = sample_data();
+
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_with(&dx, Manhattan);
+        assert_eq!(hclust.root().distance(), 110.);
+        let heights: Vec<f64> = (0..6).map(|i| hclust.linkage()[(i, 2)]).collect();
+        assert_eq!(heights, vec![22., 42., 47., 68., 83., 110.]);
+
+        let mut hclust = HClust::new(ClusterDistance::Complete);
+        hclust.fit_with(&dx, |a: &DVec<f64>, b: &DVec<f64>| Manhattan.distance(a, b));
+        assert_eq!(hclust.root().distance(), 200.);
+    }
+
+    #[test]
+    fn test_hclust_dist() {
+        let dx = sample_data();
+
+        let mut hclust = HClust::new(ClusterDistance::Average);
+        hclust.fit(&dx);
+
+        // square dissimilarity matrix
+        let square = pairwise_distances(&dx, &dx, &Euclidean);
+        let mut from_square = HClust::new(ClusterDistance::Average);
+        from_square.fit_dist(&square);
+        assert_eq!(from_square.linkage(), hclust.linkage());
+        assert_eq!(from_square.order(), hclust.order());
+
+        // condensed as a row and a column
+        let mut values: Vec<f64> = vec![];
+        for i in 0..7 {
+            for j in (i + 1)..7 {
+                values.push(square[(i, j)]);
+            }
+        }
+        let mut from_row = HClust::new(ClusterDistance::Average);
+        from_row.fit_dist(&DMat::from_row_vec(1, 21, &values));
+        assert_eq!(from_row.linkage(), hclust.linkage());
+
+        let mut from_col = HClust::new(ClusterDistance::Average);
+        from_col.fit_dist(&DMat::from_col_vec(21, 1, &values));
+        assert_eq!(from_col.linkage(), hclust.linkage());
+
+        // 2 records
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_row_vec(2, 2, &vec![0., 3., 3., 0.]));
+        assert_eq!(hclust.root().distance(), 3.);
+        assert_eq!(hclust.order(), vec![1, 0]);
+
+        // 1 x 1 matrix is a single record
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_elem(1, 1, 0.));
+        assert_eq!(hclust.root().distance(), 0.);
+        assert_eq!(hclust.order(), vec![0]);
+        assert_eq!(hclust.linkage().nrows(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dissimilarities must be finite and non-negative")]
+    fn test_hclust_dist_infinite() {
+        let dist: DMat<f64> = DMat::from_row_vec(3, 3, &vec![0., 1., f64::INFINITY,
+                                                             1., 0., 3.,
+                                                             f64::INFINITY, 3., 0.]);
+        let mut hclust = HClust::new(ClusterDistance::Complete);
+        hclust.fit_dist(&dist);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dissimilarities must be finite and non-negative")]
+    fn test_hclust_dist_negative() {
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_row_vec(1, 3, &vec![1., -2., 3.]));
+    }
+
+    #[test]
+    fn test_hclust_infinite_metric() {
+        // records in different groups are infinitely far
+        let dx: DMat<f64> = DMat::from_col_vec(4, 1, &vec![0., 1., 10., 11.]);
+        let metric = |a: &DVec<f64>, b: &DVec<f64>| {
+            if (a[0] < 5.) == (b[0] < 5.) { (a[0] - b[0]).abs() } else { f64::INFINITY }
+        };
+        let methods = vec![ClusterDistance::Single, ClusterDistance::Complete];
+        for method in methods {
+            let mut hclust = HClust::new(method);
+            hclust.fit_with(&dx, &metric);
+            let linkage = hclust.linkage();
+            assert_eq!(linkage.nrows(), 3);
+            assert_eq!(linkage[(2, 2)], f64::INFINITY);
+            assert_eq!(linkage[(2, 3)], 4.);
+            assert_eq!(hclust.cutree(2).at, vec![0, 0, 1, 1]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Dissimilarity matrix must not be empty")]
+    fn test_hclust_dist_empty() {
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_elem(0, 0, 0.));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid length of condensed dissimilarity matrix")]
+    fn test_hclust_dist_condensed_length() {
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_row_vec(1, 4, &vec![1., 2., 3., 4.]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Dissimilarity matrix must be symmetric")]
+    fn test_hclust_dist_asymmetric() {
+        let dist: DMat<f64> = DMat::from_row_vec(3, 3, &vec![0., 1., 2., 1., 0., 3., 2., 4., 0.]);
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&dist);
+    }
+
+    #[test]
+    #[should_panic(expected = "Diagonal of dissimilarity matrix must be 0")]
+    fn test_hclust_dist_diagonal() {
+        let dist: DMat<f64> = DMat::from_row_vec(3, 3, &vec![0., 1., 2., 1., 1., 3., 2., 3., 0.]);
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&dist);
+    }
+
+    #[test]
+    fn test_hclust_cophenetic() {
+        let dx = sample_data();
+
+        let methods = vec![(ClusterDistance::Single, 28.478061731796284, 0.8915766971886205),
+                           (ClusterDistance::Complete, 33.77869150810907, 0.8944868973315787),
+                           (ClusterDistance::Average, 31.128376619952675, 0.9001070791369232)];
+        for (method, d02, corr) in methods {
+            let mut hclust = HClust::new(method);
+            hclust.fit(&dx);
+
+            let coph = hclust.cophenetic();
+            assert_eq!(coph.nrows(), 7);
+            assert_eq!(coph[(0, 0)], 0.);
+            assert_eq!(coph[(1, 5)], 12.409673645990857);
+            assert_eq!(coph[(0, 2)], d02);
+            assert_eq!(coph[(2, 0)], d02);
+            assert_eq!(coph[(0, 6)], hclust.root().distance());
+            assert!((hclust.cophenetic_correlation() - corr).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_hclust_cophenetic_no_variance() {
+        let mut hclust = HClust::new(ClusterDistance::Average);
+        hclust.fit_dist(&DMat::from_row_vec(2, 2, &vec![0., 3., 3., 0.]));
+        assert!(hclust.cophenetic_correlation().is_nan());
+
+        // all pairs have the same distance
+        let mut hclust = HClust::new(ClusterDistance::Average);
+        hclust.fit_dist(&DMat::from_row_vec(1, 3, &vec![2., 2., 2.]));
+        assert_eq!(hclust.cophenetic()[(0, 2)], 2.);
+        assert!(hclust.cophenetic_correlation().is_nan());
+    }
+
+    #[test]
+    fn test_hclust_export() {
+        // (0, 1) are merged at 2, then merged with 2 at 6
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_row_vec(1, 3, &vec![2., 6., 6.]));
+
+        assert_eq!(hclust.to_newick(None), "((1:2,0:2):4,2:6);");
+        let labels = vec!["a b".to_string(), "c".to_string(), "d'e\"".to_string()];
+        assert_eq!(hclust.to_newick(Some(&labels)), r#"((c:2,'a b':2):4,'d''e"':6);"#);
+
+        let json = r#"{"id":4,"distance":6,"children":[{"id":3,"distance":2,"children":["#.to_string()
+                   + r#"{"id":1,"name":"1","distance":0},{"id":0,"name":"0","distance":0}]},"#
+                   + r#"{"id":2,"name":"2","distance":0}]}"#;
+        assert_eq!(hclust.to_json(None), json);
+        assert!(hclust.to_json(Some(&labels)).contains(r#""name":"d'e\"""#));
+
+        // leaf labels from the first column of CSV
+        let dx = sample_data();
+        let mut reader = csv::Reader::from_string(SAMPLE).has_headers(true);
+        let names = read_csv_strings(&mut reader, 0);
+        assert_eq!(names[0], "田中");
+
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit(&dx);
+        let newick = hclust.to_newick(Some(&names));
+        assert!(newick.starts_with("(("));
+        assert!(newick.ends_with(");"));
+        assert!(newick.contains("吉野:12.409673645990857"));
+    }
+
+    #[test]
+    fn test_hclust_export_inversion() {
+        // (0, 1) are merged at 2, then merged with 2 at 1.8
+        let dx: DMat<f64> = DMat::from_row_vec(3, 2, &vec![0., 0., 2., 0., 1., 1.8]);
+        let mut hclust = HClust::new(ClusterDistance::Centroid);
+        hclust.fit(&dx);
+        let newick = hclust.to_newick(None);
+        assert!(newick.starts_with("((1:2,0:2):0,2:1.7"));
+        assert!(!newick.contains('-'));
+
+        // infinite distance is not valid in JSON
+        let dx: DMat<f64> = DMat::from_col_vec(2, 1, &vec![0., 1.]);
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_with(&dx, |_: &DVec<f64>, _: &DVec<f64>| f64::INFINITY);
+        assert_eq!(hclust.to_json(None),
+                   r#"{"id":2,"distance":null,"children":[{"id":1,"name":"1","distance":0},"#
+                   .to_string() + r#"{"id":0,"name":"0","distance":0}]}"#);
+    }
+
+    #[test]
+    fn test_hclust_lance_williams() {
+        let dx = sample_data();
+
+        let methods = vec![(ClusterDistance::Weighted, 55.012725661125614, 72.69067842876035),
+                           (ClusterDistance::Ward, 62.48199740725323, 115.91869071527186),
+                           (ClusterDistance::Centroid, 51.016337252557314, 62.603248052051306),
+                           (ClusterDistance::Median, 52.76480834798891, 64.65944439909765)];
+        for (method, fifth, root) in methods {
+            let mut hclust = HClust::new(method);
+            hclust.fit(&dx);
+            // all methods merge the same clusters on this data
+            assert_eq!(hclust.order(), vec![4, 2, 0, 3, 5, 1, 6]);
+            let linkage = hclust.linkage();
+            assert!((linkage[(0, 2)] - 12.409673645990857).abs() < 1e-10);
+            assert!((linkage[(4, 2)] - fifth).abs() < 1e-10);
+            assert!((linkage[(5, 2)] - root).abs() < 1e-10);
+        }
+
+        // Centroid gives distance between centroids of {0, 2, 3, 4} and {1, 5, 6}
+        let centroid = |rows: &[usize]| -> Vec<f64> {
+            (0..5).map(|j| rows.iter().fold(0., |a, &r| a + dx[(r, j)]) / rows.len() as f64)
+                  .collect()
+        };
+        let (c1, c2) = (centroid(&[0, 2, 3, 4]), centroid(&[1, 5, 6]));
+        let d = (0..5).fold(0., |a, j| a + (c1[j] - c2[j]) * (c1[j] - c2[j])).sqrt();
+        let mut hclust = HClust::new(ClusterDistance::Centroid);
+        hclust.fit(&dx);
+        assert!((hclust.root().distance() - d).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_hclust_dendrogram() {
+        let dx = sample_data();
+
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit(&dx);
+
+        let linkage = hclust.linkage();
+        assert_eq!(linkage.nrows(), 6);
+        let exp = vec![(1., 5., 12.409673645990857, 2.),
+                       (2., 4., 21.307275752662516, 2.),
+                       (0., 8., 28.478061731796284, 3.),
+                       (6., 7., 38.1051177665153, 3.),
+                       (3., 9., 47.10626285325551, 4.),
+                       (10., 11., 54.31390245600108, 7.)];
+        for (i, &(left, right, distance, size)) in exp.iter().enumerate() {
+            assert_eq!(linkage[(i, 0)], left);
+            assert_eq!(linkage[(i, 1)], right);
+            assert!((linkage[(i, 2)] - distance).abs() < 1e-10);
+            assert_eq!(linkage[(i, 3)], size);
+        }
+
+        assert_eq!(hclust.order(), vec![4, 2, 0, 3, 5, 1, 6]);
+
+        // walk the tree
+        let root = hclust.root();
+        assert_eq!(root.id(), 12);
+        assert_eq!(root.nodes().len(), 7);
+        assert_eq!(root.distance(), 54.31390245600108);
+        assert_eq!(root.walk().len(), 13);
+
+        let leaves: Vec<usize> = root.walk().iter().filter(|c| c.is_leaf())
+                                     .map(|c| c.id()).collect();
+        assert_eq!(leaves, hclust.order());
+
+        let ref left = root.children()[0];
+        assert_eq!(left.id(), 11);
+        assert_eq!(left.children()[0].id(), 9);
+        assert_eq!(left.children()[1].id(), 3);
+        assert!(left.children()[1].is_leaf());
+        assert_eq!(root.children()[1].id(), 10);
+    }
+
+    #[test]
+    fn test_hclust_cutree() {
+        let dx = sample_data();
+
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit(&dx);
+
+        assert_eq!(hclust.cutree(1).at, vec![0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(hclust.cutree(2).at, vec![0, 1, 0, 0, 0, 1, 1]);
+        assert_eq!(hclust.cutree(3).at, vec![0, 1, 0, 2, 0, 1, 1]);
+        assert_eq!(hclust.cutree(7).at, vec![0, 1, 2, 3, 4, 5, 6]);
+
+        assert_eq!(hclust.cutree_height(0.).at, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(hclust.cutree_height(30.).at, vec![0, 1, 0, 2, 0, 1, 3]);
+        assert_eq!(hclust.cutree_height(100.).at, vec![0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Heights of merges must be increasing to cut by height")]
+    fn test_hclust_cutree_inversion() {
+        // (0, 1) are merged at 2, their centroid (1, 0) is 1.8 away from 2
+        let dx: DMat<f64> = DMat::from_row_vec(3, 2, &vec![0., 0., 2., 0., 1., 1.8]);
+        let mut hclust = HClust::new(ClusterDistance::Centroid);
+        hclust.fit(&dx);
+        assert_eq!(hclust.linkage()[(0, 2)], 2.);
+        assert!((hclust.linkage()[(1, 2)] - 1.8).abs() < 1e-10);
+        assert_eq!(hclust.cutree(2).at, vec![0, 0, 1]);
+        hclust.cutree_height(1.9);
+    }
+}
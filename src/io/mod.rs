@@ -2,31 +2,98 @@ use std::f64;
 use std::io;
 use std::str;
 use std::str::FromStr;
+use std::usize;
 use std::vec::Vec;
 
-use csv::Reader;
-use nalgebra::DMat;
+use csv::{Reader, NextField};
+use nalgebra::{DMat, DVec};
 
 //http://stackoverflow.com/questions/25272392/wrong-number-of-type-arguments-expected-1-but-found-0
 
 pub fn read_csv_f64<R: io::Read>(reader: &mut Reader<R>) -> DMat<f64> {
     // csv::Reder から f64 に変換できるカラムのみ読み込み
+    let (x, nrows) = read_records(reader, usize::MAX);
+    let ncols = x.len() / nrows;
+
+    // http://nalgebra.org/doc/nalgebra/struct.DMat.html
+    return DMat::from_row_vec(nrows, ncols, &x);
+}
+
+/// 最大 nrows レコードを読み込み (読み込むレコードがない場合は None)
+pub fn read_csv_f64_chunk<R: io::Read>(reader: &mut Reader<R>,
+                                       nrows: usize) -> Option<DMat<f64>> {
+    let (x, nrows) = read_records(reader, nrows);
+    if nrows == 0 {
+        return None;
+    }
+    let ncols = x.len() / nrows;
+    return Some(DMat::from_row_vec(nrows, ncols, &x));
+}
+
+/// 指定した列を文字列として読み込み
+pub fn read_csv_strings<R: io::Read>(reader: &mut Reader<R>, col: usize) -> Vec<String> {
+    return reader.byte_records().map(|r| r.unwrap())
+                 .map(|record| str::from_utf8(&record[col]).unwrap().trim().to_string())
+                 .collect();
+}
+
+/// 指定した列の文字列をラベル (出現順の番号) として読み込み、ラベルと対応する文字列を返す
+pub fn read_csv_labels<R: io::Read>(reader: &mut Reader<R>,
+                                    col: usize) -> (DVec<usize>, Vec<String>) {
+    let mut names: Vec<String> = vec![];
+    let mut labels: Vec<usize> = vec![];
+
+    for item in read_csv_strings(reader, col) {
+        let label = match names.iter().position(|n| *n == item) {
+            Some(i) => i,
+            None => {
+                names.push(item);
+                names.len() - 1
+            }
+        };
+        labels.push(label);
+    }
+    return (DVec::from_slice(labels.len(), &labels), names);
+}
 
+/// 最大 limit レコードの値とレコード数を返す
+fn read_records<R: io::Read>(reader: &mut Reader<R>, limit: usize) -> (Vec<f64>, usize) {
     let mut x:Vec<f64> = vec![];
     let mut nrows: usize = 0;
 
-    for record in reader.byte_records().map(|r| r.unwrap()) {
-        // f64 に変換できる列のみ読み込み
-        for item in record.iter().map(|i| str::from_utf8(i).unwrap()) {
-            match f64::from_str(item) {
-                Ok(v) => x.push(v),
-                Err(_) => {}
-            };
+    if reader.byte_offset() == 0 {
+        for record in reader.byte_records().take(limit).map(|r| r.unwrap()) {
+            for item in record.iter() {
+                push_f64(&mut x, item);
+            }
+            nrows += 1;
+        }
+    } else {
+        // byte_records は作成のたびに先頭行を返し直すため、読み込みの途中からは next_bytes を使う
+        while nrows < limit && !reader.done() {
+            let mut nfields = 0;
+            loop {
+                match reader.next_bytes() {
+                    NextField::EndOfRecord | NextField::EndOfCsv => break,
+                    NextField::Error(err) => panic!("{}", err),
+                    NextField::Data(item) => {
+                        push_f64(&mut x, item);
+                        nfields += 1;
+                    }
+                }
+            }
+            if nfields > 0 {
+                nrows += 1;
+            }
         }
-        nrows += 1;
     }
-    let ncols = x.len() / nrows;
+    return (x, nrows);
+}
 
-    // http://nalgebra.org/doc/nalgebra/struct.DMat.html
-    return DMat::from_row_vec(nrows, ncols, &x);
+/// f64 に変換できる列のみ読み込み
+fn push_f64(x: &mut Vec<f64>, item: &[u8]) {
+    match f64::from_str(str::from_utf8(item).unwrap()) {
+        Ok(v) => x.push(v),
+        Err(_) => {}
+    };
 }
@@ -0,0 +1,173 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, RowSlice};
+use std::cmp::Ordering;
+
+use super::mathfunc::euc_dist;
+
+/// k-d tree for neighbor queries in Euclid distance
+pub struct KDTree {
+    rows: Vec<DVec<f64>>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+struct Node {
+    index: usize,                   // row number of the record on the node
+    axis: usize,                    // column used to split
+    left: Option<usize>,            // records whose values are lower or equal
+    right: Option<usize>,           // records whose values are higher or equal
+}
+
+impl KDTree {
+
+    pub fn new(data: &DMat<f64>) -> KDTree {
+        let rows: Vec<DVec<f64>> = (0..data.nrows()).map(|r| data.row_slice(r, 0, data.ncols()))
+                                                    .collect();
+        let mut tree = KDTree {
+            rows: rows,
+            nodes: vec![],
+            root: None,
+        };
+        let mut indices: Vec<usize> = (0..data.nrows()).collect();
+        tree.root = tree.build(&mut indices, 0);
+        return tree;
+    }
+
+    /// split the records at the median of the axis recursively
+    fn build(&mut self, indices: &mut [usize], depth: usize) -> Option<usize> {
+        if indices.len() == 0 {
+            return None;
+        }
+        let axis = depth % self.rows[indices[0]].len();
+        {
+            let rows = &self.rows;
+            indices.sort_by(|&a, &b| {
+                rows[a][axis].partial_cmp(&rows[b][axis]).unwrap_or(Ordering::Equal)
+            });
+        }
+        let median = indices.len() / 2;
+        let (lower, rest) = indices.split_at_mut(median);
+        let (center, upper) = rest.split_at_mut(1);
+
+        let left = self.build(lower, depth + 1);
+        let right = self.build(upper, depth + 1);
+        self.nodes.push(Node {
+            index: center[0],
+            axis: axis,
+            left: left,
+            right: right,
+        });
+        return Some(self.nodes.len() - 1);
+    }
+
+    /// number of records
+    pub fn len(&self) -> usize {
+        return self.rows.len();
+    }
+
+    /// row numbers of the records within radius (inclusive), in ascending order
+    pub fn within_radius(&self, point: &DVec<f64>, radius: f64) -> Vec<usize> {
+        let mut found: Vec<usize> = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            let row = &self.rows[node.index];
+            if euc_dist(point, row) <= radius {
+                found.push(node.index);
+            }
+            let diff = point[node.axis] - row[node.axis];
+            if let Some(left) = node.left {
+                if diff <= radius {
+                    stack.push(left);
+                }
+            }
+            if let Some(right) = node.right {
+                if diff >= - radius {
+                    stack.push(right);
+                }
+            }
+        }
+        found.sort();
+        return found;
+    }
+
+    /// row numbers and distances of the k nearest records, in ascending order of distance
+    ///
+    /// Records with the same distance are ordered by row numbers.
+    pub fn nearest(&self, point: &DVec<f64>, k: usize) -> Vec<(usize, f64)> {
+        let mut found: Vec<(usize, f64)> = vec![];
+        if k > 0 {
+            if let Some(root) = self.root {
+                self.search_nearest(root, point, k, &mut found);
+            }
+        }
+        return found;
+    }
+
+    fn search_nearest(&self, n: usize, point: &DVec<f64>, k: usize,
+                      found: &mut Vec<(usize, f64)>) {
+        let node = &self.nodes[n];
+        let row = &self.rows[node.index];
+
+        // keep found sorted by (distance, row number)
+        let d = euc_dist(point, row);
+        let pos = found.iter().position(|&(i, fd)| d < fd || (d == fd && node.index < i))
+                              .unwrap_or(found.len());
+        if pos < k {
+            found.insert(pos, (node.index, d));
+            found.truncate(k);
+        }
+
+        let diff = point[node.axis] - row[node.axis];
+        let (near, far) = if diff <= 0. { (node.left, node.right) } else { (node.right, node.left) };
+        if let Some(c) = near {
+            self.search_nearest(c, point, k, found);
+        }
+        if let Some(c) = far {
+            // the other side can contain closer records only when the splitting plane is closer
+            if found.len() < k || diff.abs() <= found[found.len() - 1].1 {
+                self.search_nearest(c, point, k, found);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat, RowSlice};
+    use std::cmp::Ordering;
+    use super::KDTree;
+    use super::super::mathfunc::euc_dist;
+
+    #[test]
+    fn test_kdtree() {
+        let data: DMat<f64> = DMat::from_fn(50, 2, |i, j| ((i * 7 + j * 13 + i * j) % 11) as f64);
+        let tree = KDTree::new(&data);
+        assert_eq!(tree.len(), 50);
+
+        for i in 0..50 {
+            let point = data.row_slice(i, 0, 2);
+            let dists: Vec<f64> = (0..50).map(|r| euc_dist(&point, &data.row_slice(r, 0, 2)))
+                                         .collect();
+
+            // compare with brute force
+            let expected: Vec<usize> = (0..50).filter(|&r| dists[r] <= 3.).collect();
+            assert_eq!(tree.within_radius(&point, 3.), expected);
+
+            let mut expected: Vec<usize> = (0..50).collect();
+            expected.sort_by(|&a, &b| match dists[a].partial_cmp(&dists[b]).unwrap() {
+                Ordering::Equal => a.cmp(&b),
+                o => o
+            });
+            let nearest = tree.nearest(&point, 5);
+            assert_eq!(nearest.iter().map(|&(r, _)| r).collect::<Vec<usize>>(),
+                       expected[0..5].to_vec());
+            assert_eq!(nearest[0].1, 0.);
+        }
+
+        let nearest = tree.nearest(&DVec::from_slice(2, &vec![100., 100.]), 100);
+        assert_eq!(nearest.len(), 50);
+    }
+}
@@ -3,16 +3,35 @@ extern crate num;
 extern crate rand;
 
 use nalgebra::{DVec, DMat, RowSlice, Iterable};
-use rand::sample;
-use std::collections::HashMap;
+use rand::{sample, Rng, StdRng};
 use std::f64;
 
-use super::mathfunc::{euc_dist};
+use super::mathfunc::{euc_dist, get_rng, dmat_take_rows};
+
+/// 中心点の初期化方法
+pub enum KMeansInit {
+    Random,                 // uniform sampling of records
+    KMeansPlusPlus,         // k-means++
+}
+
+/// 中心点を更新するアルゴリズム
+pub enum KMeansAlgorithm {
+    Lloyd,                  // 各イテレーションで全ての中心点との距離を計算
+    Elkan,                  // 三角不等式による上界・下界で距離の計算を省略
+}
 
 pub struct KMeans {
     pub nclusters: usize,                   // クラスタ数
     max_iter: usize,                        // イテレーション回数
-    pub centroids: HashMap<usize, Cluster>,
+    pub tolerance: f64,                     // 収束判定に用いる中心点の移動距離
+    pub init: KMeansInit,                   // 初期化方法
+    pub algorithm: KMeansAlgorithm,         // 更新アルゴリズム
+    pub n_init: usize,                      // 初期化を繰り返す回数
+    centroids: DMat<f64>,                   // 各クラスタの中心点 (行がクラスタ)
+    pub inertia: f64,                       // 各レコードと中心点の距離の二乗和
+    pub n_iter: usize,                      // 実行したイテレーション回数
+    pub converged: bool,                    // max_iter 以内に収束したか
+    pub seed: Option<usize>,                // 乱数のシード
 }
 
 impl KMeans {
@@ -21,39 +40,166 @@ impl KMeans {
         KMeans {
             nclusters: nclusters,
             max_iter: max_iter,
-            centroids: HashMap::new(),
+            tolerance: 0.,
+            init: KMeansInit::KMeansPlusPlus,
+            algorithm: KMeansAlgorithm::Lloyd,
+            n_init: 1,
+            centroids: DMat::from_elem(0, 0, 0.),
+            inertia: f64::MAX,
+            n_iter: 0,
+            converged: false,
+            seed: None,
         }
     }
 
     pub fn fit(&mut self, data: &DMat<f64>) {
-        let mut rng = rand::thread_rng();
+        let mut rng = get_rng(self.seed);
 
-        // データからクラスタの初期値をサンプリング (非復元抽出)
-        let inits: Vec<usize> = sample(&mut rng, 0..data.nrows(), self.nclusters);
-        for (i, rownum) in inits.into_iter().enumerate() {
-            let mut c = Cluster::new(data.ncols());
-            let row = data.row_slice(rownum, 0, data.ncols());
-            c.add_element(row);
-            self.centroids.insert(i, c);
+        // n_init 回初期化し、inertia が最小のものを残す
+        let mut best: Option<(DMat<f64>, f64, usize, bool)> = None;
+        for _ in 0..self.n_init {
+            self.init_centroids(data, &mut rng);
+            let (n_iter, converged) = self.fit_once(data);
+
+            let inertia = self.get_inertia(data);
+            let is_better = match best {
+                Some((_, best_inertia, _, _)) => inertia < best_inertia,
+                None => true
+            };
+            if is_better {
+                best = Some((self.centroids.clone(), inertia, n_iter, converged));
+            }
+        }
+
+        if let Some((centroids, inertia, n_iter, converged)) = best {
+            self.centroids = centroids;
+            self.inertia = inertia;
+            self.n_iter = n_iter;
+            self.converged = converged;
         }
+    }
+
+    /// 各クラスタの中心点 (行がクラスタ)
+    pub fn centroids(&self) -> &DMat<f64> {
+        return &self.centroids;
+    }
+
+    /// クラスタの中心点
+    pub fn centroid(&self, cnum: usize) -> DVec<f64> {
+        return self.centroids.row_slice(cnum, 0, self.centroids.ncols());
+    }
+
+    /// 中心点の初期値を設定
+    fn init_centroids<R: Rng>(&mut self, data: &DMat<f64>, rng: &mut R) {
+        let inits: Vec<usize> = match self.init {
+            // データからクラスタの初期値をサンプリング (非復元抽出)
+            KMeansInit::Random => sample(rng, 0..data.nrows(), self.nclusters),
+            KMeansInit::KMeansPlusPlus => kmeans_plusplus(data, self.nclusters, rng)
+        };
+        self.centroids = DMat::from_fn(self.nclusters, data.ncols(), |c, j| data[(inits[c], j)]);
+    }
 
+    /// 初期化済みの中心点からクラスタリングを実行
+    /// イテレーション回数と収束したかどうかを返す
+    fn fit_once(&mut self, data: &DMat<f64>) -> (usize, bool) {
+        return match self.algorithm {
+            KMeansAlgorithm::Lloyd => self.fit_lloyd(data),
+            KMeansAlgorithm::Elkan => self.fit_elkan(data)
+        };
+    }
+
+    /// Lloyd のアルゴリズム
+    fn fit_lloyd(&mut self, data: &DMat<f64>) -> (usize, bool) {
         let mut cindexer = self.predict(data);
 
         // 最大 max_iter 回繰り返し
-        for _ in 0..self.max_iter {
+        for i in 0..self.max_iter {
+            let old = self.centroids.clone();
+
             // 中心点の更新
             self.update_centroids(&data, &cindexer);
             // 各レコードを クラスタの中心点にもっとも近いものに分類
             let cindexer_new = self.predict(data);
 
+            // 中心点の移動距離の最大値
+            let shift = get_shifts(&old, &self.centroids).iter().fold(0., |a: f64, b| a.max(*b));
+
             // Eq での比較結果は element-wise ではなく bool になる
-            if cindexer_new == cindexer {
+            if cindexer_new == cindexer || shift <= self.tolerance {
                 // 変化がなくなったら終了
-                break;
+                return (i + 1, true);
+            } else {
+                cindexer = cindexer_new;
+            }
+        }
+        return (self.max_iter, false);
+    }
+
+    /// Elkan のアルゴリズム
+    ///
+    /// 中心点の更新、収束判定は Lloyd と同じで、同じ分類結果になる
+    fn fit_elkan(&mut self, data: &DMat<f64>) -> (usize, bool) {
+        let nrows = data.nrows();
+        let k = self.nclusters;
+        let rows: Vec<DVec<f64>> = (0..nrows).map(|r| data.row_slice(r, 0, data.ncols()))
+                                             .collect();
+
+        // 各レコードと所属するクラスタの中心点との距離の上界
+        let mut upper = DVec::from_elem(nrows, 0.);
+        // 各レコードと各中心点との距離の下界
+        let mut lower = DMat::from_elem(nrows, k, 0.);
+
+        let mut cindexer = DVec::from_elem(nrows, 0);
+        for r in 0..nrows {
+            let mut current_dist = f64::MAX;
+            for c in 0..k {
+                let d = self.centroid_dist(&rows[r], c);
+                lower[(r, c)] = d;
+                if d < current_dist {
+                    current_dist = d;
+                    cindexer[r] = c;
+                }
+            }
+            upper[r] = current_dist;
+        }
+
+        for i in 0..self.max_iter {
+            // 中心点の更新
+            let old = self.centroids.clone();
+            self.update_centroids(&data, &cindexer);
+            let shifts = get_shifts(&old, &self.centroids);
+
+            // 中心点の移動距離だけ上界・下界を緩める
+            for r in 0..nrows {
+                upper[r] += shifts[cindexer[r]];
+                for c in 0..k {
+                    lower[(r, c)] = (lower[(r, c)] - shifts[c]).max(0.);
+                }
+            }
+
+            let cindexer_new = elkan_assign(&rows, &self.centroids, &cindexer,
+                                            &mut upper, &mut lower);
+
+            let shift = shifts.iter().fold(0., |a: f64, b| a.max(*b));
+            if cindexer_new == cindexer || shift <= self.tolerance {
+                return (i + 1, true);
             } else {
                 cindexer = cindexer_new;
             }
         }
+        return (self.max_iter, false);
+    }
+
+    /// 各レコードと所属するクラスタの中心点との距離の二乗和
+    fn get_inertia(&self, data: &DMat<f64>) -> f64 {
+        let mut inertia = 0.;
+        for rownum in 0..data.nrows() {
+            let row = data.row_slice(rownum, 0, data.ncols());
+            let cnum = self.get_nearest(&row);
+            let d = self.centroid_dist(&row, cnum);
+            inertia += d * d;
+        }
+        return inertia;
     }
 
     /// 各レコードが所属するクラスタのベクトルを返す
@@ -68,65 +214,397 @@ impl KMeans {
         let mut tmp_i = 0;
         let mut current_dist = f64::MAX;
 
-        for (cnum, cluster) in &self.centroids {
-            let d = euc_dist(values, &cluster.centroid);
+        // 同じ距離の場合はラベルの小さいクラスタに分類
+        for cnum in 0..self.nclusters {
+            let d = self.centroid_dist(values, cnum);
             if d < current_dist {
                 current_dist = d;
-                tmp_i = *cnum;
+                tmp_i = cnum;
             }
         }
         return tmp_i;
     }
 
+    /// レコードと中心点の距離 (euc_dist と同じ値を、中心点をコピーせずに計算)
+    fn centroid_dist(&self, values: &DVec<f64>, cnum: usize) -> f64 {
+        assert!(values.len() == self.centroids.ncols());
+        let mut val = 0.;
+        for j in 0..values.len() {
+            let d = values[j] - self.centroids[(cnum, j)];
+            val += d * d;
+        }
+        return val.sqrt();
+    }
+
     /// クラスタの中心点を更新する
     fn update_centroids(&mut self, data: &DMat<f64>, cindexer: &DVec<usize>) {
-        self.centroids.clear();
+        let ncols = data.ncols();
+        let mut sums = DMat::from_elem(self.nclusters, ncols, 0.);
+        let mut counts = vec![0.; self.nclusters];
 
-        for i in 0..self.nclusters {
-            let c = Cluster::new(data.ncols());
-            self.centroids.insert(i, c);
+        for (rownum, &cnum) in cindexer.iter().enumerate() {
+            for j in 0..ncols {
+                sums[(cnum, j)] += data[(rownum, j)];
+            }
+            counts[cnum] += 1.;
         }
 
-        for (rownum, cnum) in cindexer.iter().enumerate() {
+        // 空のクラスタの中心点はいったん 0 とする
+        let mut empty: Vec<usize> = vec![];
+        for cnum in 0..self.nclusters {
+            if counts[cnum] == 0. {
+                empty.push(cnum);
+            } else {
+                for j in 0..ncols {
+                    sums[(cnum, j)] /= counts[cnum];
+                }
+            }
+        }
+        self.centroids = sums;
+
+        if empty.len() > 0 {
+            self.reseed_empty_clusters(data, cindexer, &empty, &mut counts);
+        }
+    }
+
+    /// 空のクラスタの中心点を、所属するクラスタの中心点から遠いレコードで置き換える
+    ///
+    /// 移したレコードは元のクラスタから除き、その中心点を再計算する
+    fn reseed_empty_clusters(&mut self, data: &DMat<f64>, cindexer: &DVec<usize>,
+                             empty: &[usize], counts: &mut Vec<f64>) {
+        let mut dists: Vec<(usize, f64)> = (0..data.nrows()).map(|rownum| {
             let row = data.row_slice(rownum, 0, data.ncols());
-            let mut c = self.centroids.remove(&cnum).unwrap();
-            c.add_element(row);
-            self.centroids.insert(*cnum, c);
+            (rownum, self.centroid_dist(&row, cindexer[rownum]))
+        }).collect();
+        // 距離の降順
+        dists.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+        for (&cnum, &(rownum, _)) in empty.iter().zip(dists.iter()) {
+            let donor = cindexer[rownum];
+            // レコードが 1 つのクラスタは空にしない
+            if counts[donor] > 1. {
+                for j in 0..data.ncols() {
+                    let c = self.centroids[(donor, j)];
+                    self.centroids[(donor, j)] = (c * counts[donor] - data[(rownum, j)])
+                                                 / (counts[donor] - 1.);
+                }
+                counts[donor] -= 1.;
+            }
+            for j in 0..data.ncols() {
+                self.centroids[(cnum, j)] = data[(rownum, j)];
+            }
+            counts[cnum] = 1.;
         }
+    }
+}
 
-        for cnum in 0..self.nclusters {
-            let mut c = self.centroids.remove(&cnum).unwrap();
-            c.finalize();
-            self.centroids.insert(cnum, c);
+/// 各中心点の移動距離
+fn get_shifts(old: &DMat<f64>, new: &DMat<f64>) -> Vec<f64> {
+    return (0..old.nrows()).map(|c| {
+        euc_dist(&old.row_slice(c, 0, old.ncols()), &new.row_slice(c, 0, new.ncols()))
+    }).collect();
+}
+
+/// Elkan のアルゴリズムによる各レコードの分類
+///
+/// 距離の上界・下界と中心点間の距離から、最も近い中心点になりえないものの計算を省略する。
+/// 同じ距離の場合は Lloyd と同じくラベルの小さいクラスタに分類する
+fn elkan_assign(rows: &[DVec<f64>], centers: &DMat<f64>, cindexer: &DVec<usize>,
+                upper: &mut DVec<f64>, lower: &mut DMat<f64>) -> DVec<usize> {
+    let k = centers.nrows();
+    let cvecs: Vec<DVec<f64>> = (0..k).map(|c| centers.row_slice(c, 0, centers.ncols()))
+                                      .collect();
+    // 中心点間の距離
+    let cdists = DMat::from_fn(k, k, |a, b| euc_dist(&cvecs[a], &cvecs[b]));
+    // 他の中心点との距離の最小値の半分
+    let s: Vec<f64> = (0..k).map(|a| {
+        (0..k).filter(|&b| b != a).fold(f64::MAX, |m, b| m.min(cdists[(a, b)])) / 2.
+    }).collect();
+
+    let mut cindexer_new = cindexer.clone();
+    for r in 0..rows.len() {
+        let mut cx = cindexer[r];
+        if upper[r] < s[cx] {
+            continue;
+        }
+
+        // 上界が実際の距離と一致しているか
+        let mut tight = false;
+        for c in 0..k {
+            if c == cx || upper[r] < lower[(r, c)] || upper[r] < cdists[(cx, c)] / 2. {
+                continue;
+            }
+            if !tight {
+                let d = euc_dist(&rows[r], &cvecs[cx]);
+                upper[r] = d;
+                lower[(r, cx)] = d;
+                tight = true;
+                if upper[r] < lower[(r, c)] || upper[r] < cdists[(cx, c)] / 2. {
+                    continue;
+                }
+            }
+            let d = euc_dist(&rows[r], &cvecs[c]);
+            lower[(r, c)] = d;
+            if d < upper[r] || (d == upper[r] && c < cx) {
+                cx = c;
+                upper[r] = d;
+            }
+        }
+        cindexer_new[r] = cx;
+    }
+    return cindexer_new;
+}
+
+/// ミニバッチ k-means
+///
+/// バッチごとに中心点を逐次更新するため、全データをメモリに載せる必要がない
+pub struct MiniBatchKMeans {
+    pub kmeans: KMeans,                     // 中心点を保持し、predict に利用
+    pub batch_size: usize,                  // fit でサンプリングするバッチのサイズ
+    counts: Vec<f64>,                       // 各クラスタのこれまでのレコード数
+    rng: Option<StdRng>,
+}
+
+impl MiniBatchKMeans {
+
+    pub fn new(nclusters: usize, batch_size: usize, max_iter: usize) -> MiniBatchKMeans {
+        MiniBatchKMeans {
+            kmeans: KMeans::new(nclusters, max_iter),
+            batch_size: batch_size,
+            counts: vec![],
+            rng: None,
+        }
+    }
+
+    /// データからサンプリングしたバッチで max_iter 回更新
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        // 同じ seed なら同じバッチをサンプリングするよう、乱数も初期化
+        self.rng = None;
+        self.kmeans.centroids = DMat::from_elem(0, 0, 0.);
+        self.kmeans.n_iter = 0;
+        let batch_size = self.batch_size.min(data.nrows());
+
+        for _ in 0..self.kmeans.max_iter {
+            let indices: Vec<usize> = sample(self.get_rng(), 0..data.nrows(), batch_size);
+            self.partial_fit(&dmat_take_rows(data, &indices));
+        }
+        self.kmeans.inertia = self.kmeans.get_inertia(data);
+    }
+
+    /// 1 バッチ分、中心点を更新
+    pub fn partial_fit(&mut self, batch: &DMat<f64>) {
+        if self.kmeans.centroids.nrows() == 0 {
+            // 最初のバッチから初期値を選択
+            assert!(batch.nrows() >= self.kmeans.nclusters,
+                    "First batch must have at least as many records as clusters");
+            if self.rng.is_none() {
+                self.rng = Some(get_rng(self.kmeans.seed));
+            }
+            self.kmeans.init_centroids(batch, self.rng.as_mut().unwrap());
+            // 初期値はレコード数に含めない
+            self.counts = vec![0.; self.kmeans.nclusters];
+        }
+
+        // 中心点を逐次更新 (これまでのレコード数による移動平均)
+        let cindexer = self.kmeans.predict(batch);
+        for (rownum, &cnum) in cindexer.iter().enumerate() {
+            self.counts[cnum] += 1.;
+            for j in 0..batch.ncols() {
+                let c = self.kmeans.centroids[(cnum, j)];
+                self.kmeans.centroids[(cnum, j)] = c + (batch[(rownum, j)] - c) / self.counts[cnum];
+            }
+        }
+        self.kmeans.n_iter += 1;
+    }
+
+    /// 各レコードが所属するクラスタのベクトルを返す
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<usize> {
+        return self.kmeans.predict(data);
+    }
+
+    fn get_rng(&mut self) -> &mut StdRng {
+        if self.rng.is_none() {
+            self.rng = Some(get_rng(self.kmeans.seed));
         }
+        return self.rng.as_mut().unwrap();
     }
 }
 
-pub struct Cluster {
-    pub centroid: DVec<f64>,
-    n: f64
+/// k-means++ による初期値の選択
+///
+/// 既に選択した中心点からの距離の二乗に比例する確率でレコードを選択する
+fn kmeans_plusplus<R: Rng>(data: &DMat<f64>, nclusters: usize, rng: &mut R) -> Vec<usize> {
+    let nrows = data.nrows();
+    let ncols = data.ncols();
+
+    let mut inits: Vec<usize> = vec![rng.gen_range(0, nrows)];
+    // 最も近い中心点との距離の二乗
+    let first = data.row_slice(inits[0], 0, ncols);
+    let mut dists: Vec<f64> = (0..nrows).map(|i| {
+        let d = euc_dist(&data.row_slice(i, 0, ncols), &first);
+        d * d
+    }).collect();
+
+    while inits.len() < nclusters {
+        let total = dists.iter().fold(0., |a, b| a + b);
+        let mut next = nrows - 1;
+        if total > 0. {
+            let threshold = rng.gen::<f64>() * total;
+            let mut cumsum = 0.;
+            for (i, d) in dists.iter().enumerate() {
+                cumsum += *d;
+                if cumsum > threshold {
+                    next = i;
+                    break;
+                }
+            }
+        } else {
+            // 全てのレコードが中心点と一致する場合は未選択のものから選ぶ
+            next = (0..nrows).filter(|i| !inits.contains(i)).next().unwrap();
+        }
+        inits.push(next);
+
+        let row = data.row_slice(next, 0, ncols);
+        for i in 0..nrows {
+            let d = euc_dist(&data.row_slice(i, 0, ncols), &row);
+            if d * d < dists[i] {
+                dists[i] = d * d;
+            }
+        }
+    }
+    return inits;
 }
 
-impl Cluster {
+#[cfg(test)]
+mod tests {
+    extern crate csv;
+    use nalgebra::{DVec, DMat};
+    use super::{KMeans, KMeansInit, KMeansAlgorithm, MiniBatchKMeans};
+    use super::super::io::read_csv_f64_chunk;
+
+    #[test]
+    fn test_kmeans() {
+        let data: DMat<f64> = DMat::from_row_vec(4, 2, &vec![0., 0., 0., 1., 10., 10., 10., 11.]);
+
+        let mut kmeans = KMeans::new(2, 100);
+        kmeans.n_init = 3;
+        kmeans.seed = Some(1);
+        kmeans.fit(&data);
+        assert_eq!(kmeans.inertia, 1.);
+
+        let predicted = kmeans.predict(&data);
+        assert_eq!(predicted[0], predicted[1]);
+        assert_eq!(predicted[2], predicted[3]);
+        assert!(predicted[0] != predicted[2]);
+
+        let mut kmeans = KMeans::new(2, 100);
+        kmeans.init = KMeansInit::Random;
+        kmeans.n_init = 10;
+        kmeans.seed = Some(1);
+        kmeans.fit(&data);
+        assert_eq!(kmeans.inertia, 1.);
+        assert!(kmeans.converged);
+        assert!(kmeans.n_iter <= 100);
+    }
+
+    #[test]
+    fn test_kmeans_elkan() {
+        let data: DMat<f64> = DMat::from_fn(60, 3, |i, j| ((i * 13 + j * 7) % 17) as f64 + (i % 3) as f64 * 10.);
+
+        for seed in 0..5 {
+            let mut lloyd = KMeans::new(5, 100);
+            lloyd.seed = Some(seed);
+            lloyd.fit(&data);
 
-    fn new(ncols: usize) -> Cluster {
-        Cluster {
-            centroid: DVec::from_elem(ncols, 0.),
-            n: 0.
+            let mut elkan = KMeans::new(5, 100);
+            elkan.algorithm = KMeansAlgorithm::Elkan;
+            elkan.seed = Some(seed);
+            elkan.fit(&data);
+
+            assert_eq!(lloyd.predict(&data), elkan.predict(&data));
+            assert_eq!(lloyd.n_iter, elkan.n_iter);
+            assert_eq!(lloyd.centroids(), elkan.centroids());
         }
     }
 
-    /// 中心点を更新 (レコードを合計に追加する)
-    fn add_element(&mut self, values: DVec<f64>) {
-        self.centroid = self.centroid.clone() + values;
-        self.n = self.n + 1.;
+    #[test]
+    fn test_kmeans_empty_cluster() {
+        let data: DMat<f64> = DMat::from_row_vec(4, 2, &vec![0., 0., 0., 1., 10., 10., 10., 12.]);
+
+        // すべてのレコードを同じクラスタに割り当てると、もう一方のクラスタは空になる
+        let mut kmeans = KMeans::new(2, 100);
+        kmeans.update_centroids(&data, &DVec::from_slice(4, &vec![0, 0, 0, 0]));
+        // 中心点 (5, 5.75) から最も遠いレコードで置き換えられ、
+        // 元のクラスタの中心点は残りのレコードから再計算される
+        assert_eq!(kmeans.centroid(1), DVec::from_slice(2, &vec![10., 12.]));
+        assert!((kmeans.centroid(0)[0] - 10. / 3.).abs() < 1e-10);
+        assert!((kmeans.centroid(0)[1] - 11. / 3.).abs() < 1e-10);
+
+        let (n_iter, converged) = kmeans.fit_once(&data);
+        assert!(converged);
+        assert!(n_iter < 100);
+        assert_eq!(kmeans.get_inertia(&data), 2.5);
+    }
+
+    #[test]
+    fn test_kmeans_seed() {
+        let data: DMat<f64> = DMat::from_fn(30, 2, |i, j| ((i * 7 + j * 3) % 11) as f64);
+
+        let mut kmeans1 = KMeans::new(4, 100);
+        kmeans1.seed = Some(1);
+        kmeans1.fit(&data);
+
+        let mut kmeans2 = KMeans::new(4, 100);
+        kmeans2.seed = Some(1);
+        kmeans2.fit(&data);
+
+        assert_eq!(kmeans1.centroids(), kmeans2.centroids());
+        assert_eq!(kmeans1.predict(&data), kmeans2.predict(&data));
     }
 
-    /// 中心点を更新 (レコード数で割り、中心点を求める)
-    fn finalize(&mut self) {
-        if self.n != 0. {
-            self.centroid = self.centroid.clone() / self.n;
-            self.n = 0.;
+    #[test]
+    fn test_minibatch_kmeans() {
+        let data = "0,0\n10,10\n0,1\n10,11\n1,0\n11,10\n1,1\n11,11";
+
+        let mut reader = csv::Reader::from_string(data).has_headers(false);
+        let mut kmeans = MiniBatchKMeans::new(2, 4, 100);
+        kmeans.kmeans.seed = Some(1);
+        // 2 レコードずつ読み込んで更新
+        while let Some(chunk) = read_csv_f64_chunk(&mut reader, 2) {
+            assert_eq!(chunk.nrows(), 2);
+            kmeans.partial_fit(&chunk);
+        }
+        assert_eq!(kmeans.kmeans.n_iter, 4);
+
+        let x: DMat<f64> = DMat::from_row_vec(2, 2, &vec![0.5, 0.5, 10.5, 10.5]);
+        let predicted = kmeans.predict(&x);
+        assert!(predicted[0] != predicted[1]);
+
+        let x: DMat<f64> = DMat::from_row_vec(8, 2, &vec![0., 0., 10., 10., 0., 1., 10., 11.,
+                                                          1., 0., 11., 10., 1., 1., 11., 11.]);
+        let mut kmeans = MiniBatchKMeans::new(2, 4, 50);
+        kmeans.kmeans.seed = Some(1);
+        kmeans.fit(&x);
+        let predicted = kmeans.predict(&x);
+        for i in 0..4 {
+            assert_eq!(predicted[2 * i], predicted[0]);
+            assert_eq!(predicted[2 * i + 1], predicted[1]);
         }
+        assert!(predicted[0] != predicted[1]);
+
+        // 再度 fit しても同じ結果
+        let centroids = kmeans.kmeans.centroids().clone();
+        let inertia = kmeans.kmeans.inertia;
+        kmeans.fit(&x);
+        assert!(kmeans.kmeans.centroids() == &centroids);
+        assert_eq!(kmeans.kmeans.inertia, inertia);
+    }
+
+    #[test]
+    #[should_panic(expected = "First batch must have at least as many records as clusters")]
+    fn test_minibatch_kmeans_small_batch() {
+        let x: DMat<f64> = DMat::from_row_vec(2, 2, &vec![0., 0., 10., 10.]);
+        let mut kmeans = MiniBatchKMeans::new(3, 2, 50);
+        kmeans.partial_fit(&x);
     }
 }
@@ -0,0 +1,321 @@
+extern crate nalgebra;
+extern crate rand;
+
+use nalgebra::{DVec, DMat, RowSlice};
+use rand::sample;
+use std::f64;
+
+use super::mathfunc::{get_rng, dmat_take_rows};
+use super::mathfunc::distance::{Metric, Euclidean, pairwise_distances};
+
+/// k-medoids clustering by PAM (Partitioning Around Medoids)
+pub struct KMedoids {
+    pub nclusters: usize,
+    max_iter: usize,
+    pub medoids: Vec<usize>,                // row numbers of medoids
+    pub centers: Vec<DVec<f64>>,            // records of medoids (empty when fitted from distances)
+    pub labels: DVec<usize>,                // cluster of each record
+    pub cost: f64,                          // sum of distances to the nearest medoid
+}
+
+impl KMedoids {
+
+    pub fn new(nclusters: usize, max_iter: usize) -> KMedoids {
+        KMedoids {
+            nclusters: nclusters,
+            max_iter: max_iter,
+            medoids: vec![],
+            centers: vec![],
+            labels: DVec::from_elem(1, 0),
+            cost: f64::MAX,
+        }
+    }
+
+    /// fit using Euclid distance
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        self.fit_with(data, Euclidean);
+    }
+
+    /// fit using the specified metric
+    pub fn fit_with<M: Metric>(&mut self, data: &DMat<f64>, metric: M) {
+        let dist_mat = pairwise_distances(data, data, &metric);
+        self.fit_dist(&dist_mat);
+        self.centers = self.medoids.iter().map(|&m| data.row_slice(m, 0, data.ncols())).collect();
+    }
+
+    /// fit from a square dissimilarity matrix
+    pub fn fit_dist(&mut self, dist_mat: &DMat<f64>) {
+        assert!(dist_mat.nrows() == dist_mat.ncols(), "Distance matrix must be square");
+        assert!(self.nclusters <= dist_mat.nrows());
+
+        self.centers = vec![];
+        self.medoids = build(dist_mat, self.nclusters);
+        self.swap(dist_mat);
+
+        let (labels, cost) = assign(dist_mat, &self.medoids);
+        self.labels = labels;
+        self.cost = cost;
+    }
+
+    /// SWAP phase, replace a medoid with a non-medoid while the cost decreases
+    fn swap(&mut self, dist_mat: &DMat<f64>) {
+        let n = dist_mat.nrows();
+
+        for _ in 0..self.max_iter {
+            // distances to the nearest and the second nearest medoids
+            let mut nearest = vec![f64::MAX; n];
+            let mut second = vec![f64::MAX; n];
+            for j in 0..n {
+                for &m in &self.medoids {
+                    let d = dist_mat[(j, m)];
+                    if d < nearest[j] {
+                        second[j] = nearest[j];
+                        nearest[j] = d;
+                    } else if d < second[j] {
+                        second[j] = d;
+                    }
+                }
+            }
+
+            let mut best_change = 0.;
+            let mut best_swap: Option<(usize, usize)> = None;
+            for (mi, &m) in self.medoids.iter().enumerate() {
+                for h in 0..n {
+                    if self.medoids.contains(&h) {
+                        continue;
+                    }
+                    // change of the cost when m is replaced by h
+                    let mut change = 0.;
+                    for j in 0..n {
+                        let djh = dist_mat[(j, h)];
+                        if dist_mat[(j, m)] == nearest[j] {
+                            change += djh.min(second[j]) - nearest[j];
+                        } else {
+                            change += (djh - nearest[j]).min(0.);
+                        }
+                    }
+                    if change < best_change {
+                        best_change = change;
+                        best_swap = Some((mi, h));
+                    }
+                }
+            }
+
+            match best_swap {
+                Some((mi, h)) => self.medoids[mi] = h,
+                None => break
+            }
+        }
+    }
+
+    /// cluster of each record using Euclid distance to the medoids
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<usize> {
+        return self.predict_with(data, Euclidean);
+    }
+
+    /// cluster of each record using the specified metric
+    pub fn predict_with<M: Metric>(&self, data: &DMat<f64>, metric: M) -> DVec<usize> {
+
+        assert!(self.centers.len() > 0, "KMedoids fitted from distances can't predict new data");
+        return DVec::from_fn(data.nrows(), |r| {
+            let row = data.row_slice(r, 0, data.ncols());
+            let mut tmp_i = 0;
+            let mut current_dist = f64::MAX;
+            for (c, center) in self.centers.iter().enumerate() {
+                let d = metric.distance(&row, center);
+                if d < current_dist {
+                    current_dist = d;
+                    tmp_i = c;
+                }
+            }
+            tmp_i
+        });
+    }
+}
+
+/// BUILD phase, greedily select medoids which decrease the cost most
+fn build(dist_mat: &DMat<f64>, nclusters: usize) -> Vec<usize> {
+    let n = dist_mat.nrows();
+
+    // the first medoid minimizes the sum of distances
+    let mut first = 0;
+    let mut current = f64::MAX;
+    for i in 0..n {
+        let total = (0..n).fold(0., |a, j| a + dist_mat[(i, j)]);
+        if total < current {
+            current = total;
+            first = i;
+        }
+    }
+
+    let mut medoids = vec![first];
+    let mut nearest: Vec<f64> = (0..n).map(|j| dist_mat[(j, first)]).collect();
+
+    while medoids.len() < nclusters {
+        let mut best = n;
+        let mut best_gain = f64::NEG_INFINITY;
+        for i in 0..n {
+            if medoids.contains(&i) {
+                continue;
+            }
+            let gain = (0..n).fold(0., |a, j| a + (nearest[j] - dist_mat[(j, i)]).max(0.));
+            if gain > best_gain {
+                best_gain = gain;
+                best = i;
+            }
+        }
+        medoids.push(best);
+        for j in 0..n {
+            nearest[j] = nearest[j].min(dist_mat[(j, best)]);
+        }
+    }
+    return medoids;
+}
+
+/// labels (position in medoids) of the nearest medoid, and the total cost
+fn assign(dist_mat: &DMat<f64>, medoids: &[usize]) -> (DVec<usize>, f64) {
+    let mut cost = 0.;
+    let labels = DVec::from_fn(dist_mat.nrows(), |j| {
+        let mut tmp_i = 0;
+        let mut current_dist = f64::MAX;
+        for (c, &m) in medoids.iter().enumerate() {
+            if dist_mat[(j, m)] < current_dist {
+                current_dist = dist_mat[(j, m)];
+                tmp_i = c;
+            }
+        }
+        cost += current_dist;
+        tmp_i
+    });
+    return (labels, cost);
+}
+
+/// CLARA (Clustering LARge Applications)
+///
+/// Runs PAM on random samples and keeps the medoids with the lowest cost on the whole data.
+pub struct Clara {
+    pub kmedoids: KMedoids,                 // result of the best sample, medoids refer to whole data
+    pub nsamples: usize,                    // number of samples
+    pub sample_size: usize,
+    pub seed: Option<usize>,
+}
+
+impl Clara {
+
+    pub fn new(nclusters: usize, max_iter: usize) -> Clara {
+        Clara {
+            kmedoids: KMedoids::new(nclusters, max_iter),
+            nsamples: 5,
+            sample_size: 40 + 2 * nclusters,
+            seed: None,
+        }
+    }
+
+    /// fit using Euclid distance
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        self.fit_with(data, Euclidean);
+    }
+
+    /// fit using the specified metric
+    pub fn fit_with<M: Metric>(&mut self, data: &DMat<f64>, metric: M) {
+        let n = data.nrows();
+        let sample_size = self.sample_size.min(n);
+        let mut rng = get_rng(self.seed);
+
+        let mut best: Option<(Vec<usize>, DVec<usize>, f64)> = None;
+        for _ in 0..self.nsamples {
+            let mut indices: Vec<usize> = sample(&mut rng, 0..n, sample_size);
+            indices.sort();
+
+            let sample_data = dmat_take_rows(data, &indices);
+            let mut pam = KMedoids::new(self.kmedoids.nclusters, self.kmedoids.max_iter);
+            pam.fit_dist(&pairwise_distances(&sample_data, &sample_data, &metric));
+            let medoids: Vec<usize> = pam.medoids.iter().map(|&m| indices[m]).collect();
+
+            // cost on the whole data
+            let dist_mat = pairwise_distances(data, &dmat_take_rows(data, &medoids), &metric);
+            let (labels, cost) = assign(&dist_mat, &(0..medoids.len()).collect::<Vec<usize>>());
+
+            let is_better = match best {
+                Some((_, _, best_cost)) => cost < best_cost,
+                None => true
+            };
+            if is_better {
+                best = Some((medoids, labels, cost));
+            }
+        }
+
+        if let Some((medoids, labels, cost)) = best {
+            self.kmedoids.centers = medoids.iter().map(|&m| data.row_slice(m, 0, data.ncols()))
+                                              .collect();
+            self.kmedoids.medoids = medoids;
+            self.kmedoids.labels = labels;
+            self.kmedoids.cost = cost;
+        }
+    }
+
+    /// cluster of each record using Euclid distance to the medoids
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<usize> {
+        return self.kmedoids.predict(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{KMedoids, Clara};
+    use super::super::mathfunc::euc_dist;
+    use super::super::mathfunc::distance::Manhattan;
+
+    #[test]
+    fn test_kmedoids() {
+        let data: DMat<f64> = DMat::from_row_vec(6, 1, &vec![0., 1., 2., 10., 11., 30.]);
+
+        let mut kmedoids = KMedoids::new(2, 100);
+        kmedoids.fit(&data);
+        let mut medoids = kmedoids.medoids.clone();
+        medoids.sort();
+        assert_eq!(medoids, vec![2, 5]);
+        // 2 + 1 + 0 + 8 + 9 + 0
+        assert_eq!(kmedoids.cost, 20.);
+        assert_eq!(kmedoids.predict(&data), kmedoids.labels);
+
+        // from dissimilarity matrix
+        let dist_mat = DMat::from_fn(6, 6, |i, j| (data[(i, 0)] - data[(j, 0)]).abs());
+        let mut kmedoids = KMedoids::new(3, 100);
+        kmedoids.fit_dist(&dist_mat);
+        let mut medoids = kmedoids.medoids.clone();
+        medoids.sort();
+        assert_eq!(medoids, vec![1, 3, 5]);
+        assert_eq!(kmedoids.cost, 3.);
+
+        // custom distance
+        let mut kmedoids = KMedoids::new(2, 100);
+        kmedoids.fit_with(&data, |a: &DVec<f64>, b: &DVec<f64>| euc_dist(a, b) * 2.);
+        assert_eq!(kmedoids.cost, 40.);
+
+        // Manhattan distance is same as Euclid distance for 1 column
+        let mut kmedoids = KMedoids::new(2, 100);
+        kmedoids.fit_with(&data, Manhattan);
+        assert_eq!(kmedoids.cost, 20.);
+        assert_eq!(kmedoids.predict_with(&data, Manhattan), kmedoids.labels);
+    }
+
+    #[test]
+    fn test_clara() {
+        let data: DMat<f64> = DMat::from_fn(100, 2, |i, j| {
+            ((i * 7 + j * 3) % 5) as f64 + if i < 50 { 0. } else { 100. }
+        });
+
+        let mut clara = Clara::new(2, 100);
+        clara.sample_size = 20;
+        clara.seed = Some(1);
+        clara.fit(&data);
+
+        let labels = clara.predict(&data);
+        assert_eq!(labels, clara.kmedoids.labels);
+        for i in 0..100 {
+            assert_eq!(labels[i] == labels[0], i < 50);
+        }
+    }
+}
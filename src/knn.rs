@@ -0,0 +1,327 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, RowSlice};
+use std::cmp::Ordering;
+
+use super::kdtree::KDTree;
+use super::mathfunc::distance::Metric;
+
+/// Weights of neighbors used in prediction
+pub enum KNNWeights {
+    Uniform,                            // same weight for all neighbors
+    Distance,                           // inverse of the distance
+}
+
+/// training records and neighbor queries shared by classifier and regressor
+struct Neighbors {
+    rows: Vec<DVec<f64>>,
+    tree: Option<KDTree>,               // used for Euclid distance
+}
+
+impl Neighbors {
+
+    fn new() -> Neighbors {
+        Neighbors {
+            rows: vec![],
+            tree: None,
+        }
+    }
+
+    fn fit(&mut self, data: &DMat<f64>) {
+        self.rows = (0..data.nrows()).map(|r| data.row_slice(r, 0, data.ncols())).collect();
+        self.tree = Some(KDTree::new(data));
+    }
+
+    /// row numbers and distances of the k nearest training records for each record,
+    /// in ascending order of distance (ties are ordered by row numbers)
+    fn nearest(&self, data: &DMat<f64>, k: usize) -> Vec<Vec<(usize, f64)>> {
+        let tree = match self.tree {
+            Some(ref tree) => tree,
+            None => panic!("KNN is not fitted")
+        };
+        assert!(k <= self.rows.len(), "k must be the number of training records or less");
+        return (0..data.nrows()).map(|r| tree.nearest(&data.row_slice(r, 0, data.ncols()), k))
+                                .collect();
+    }
+
+    /// same as nearest, using the specified metric
+    fn nearest_with<M: Metric>(&self, data: &DMat<f64>, k: usize,
+                               metric: &M) -> Vec<Vec<(usize, f64)>> {
+        assert!(self.tree.is_some(), "KNN is not fitted");
+        assert!(k <= self.rows.len(), "k must be the number of training records or less");
+        return (0..data.nrows()).map(|r| {
+            let row = data.row_slice(r, 0, data.ncols());
+            let mut found: Vec<(usize, f64)> = self.rows.iter().enumerate()
+                                                   .map(|(i, x)| (i, metric.distance(&row, x)))
+                                                   .collect();
+            found.sort_by(|&(i, a), &(j, b)| match a.partial_cmp(&b).unwrap() {
+                Ordering::Equal => i.cmp(&j),
+                o => o
+            });
+            found.truncate(k);
+            found
+        }).collect();
+    }
+}
+
+/// row numbers (rows: records, columns: neighbors) and distances of neighbors
+fn to_matrices(found: Vec<Vec<(usize, f64)>>, k: usize) -> (DMat<usize>, DMat<f64>) {
+    let indices = DMat::from_fn(found.len(), k, |r, c| found[r][c].0);
+    let distances = DMat::from_fn(found.len(), k, |r, c| found[r][c].1);
+    return (indices, distances);
+}
+
+/// normalized weights of the neighbors
+///
+/// With distance weights, neighbors at distance 0 share the whole weight if exist.
+fn neighbor_weights(neighbors: &[(usize, f64)], weights: &KNNWeights) -> Vec<f64> {
+    let w: Vec<f64> = match *weights {
+        KNNWeights::Uniform => neighbors.iter().map(|_| 1.).collect(),
+        KNNWeights::Distance => {
+            if neighbors.iter().any(|&(_, d)| d == 0.) {
+                neighbors.iter().map(|&(_, d)| if d == 0. { 1. } else { 0. }).collect()
+            } else {
+                neighbors.iter().map(|&(_, d)| 1. / d).collect()
+            }
+        }
+    };
+    let total = w.iter().fold(0., |a, v| a + v);
+    return w.iter().map(|v| v / total).collect();
+}
+
+/// k-nearest neighbors classifier
+pub struct KNeighborsClassifier {
+    pub k: usize,
+    pub weights: KNNWeights,
+    pub classes: Vec<usize>,                // distinct labels in ascending order
+    neighbors: Neighbors,
+    y: Vec<usize>,                          // position of the label in classes
+}
+
+impl KNeighborsClassifier {
+
+    pub fn new(k: usize) -> KNeighborsClassifier {
+        assert!(k > 0, "k must be 1 or more");
+        KNeighborsClassifier {
+            k: k,
+            weights: KNNWeights::Uniform,
+            classes: vec![],
+            neighbors: Neighbors::new(),
+            y: vec![],
+        }
+    }
+
+    pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<usize>) {
+        assert!(data.nrows() == y.len(), "Data and labels must have the same length");
+        let mut classes = y.at.clone();
+        classes.sort();
+        classes.dedup();
+        self.y = y.at.iter().map(|l| classes.binary_search(l).unwrap()).collect();
+        self.classes = classes;
+        self.neighbors.fit(data);
+    }
+
+    /// row numbers and Euclid distances of the k nearest training records
+    pub fn kneighbors(&self, data: &DMat<f64>, k: usize) -> (DMat<usize>, DMat<f64>) {
+        return to_matrices(self.neighbors.nearest(data, k), k);
+    }
+
+    /// row numbers and distances of the k nearest training records using the specified metric
+    pub fn kneighbors_with<M: Metric>(&self, data: &DMat<f64>, k: usize,
+                                      metric: M) -> (DMat<usize>, DMat<f64>) {
+        return to_matrices(self.neighbors.nearest_with(data, k, &metric), k);
+    }
+
+    /// probability of each class (columns, in the order of classes) using Euclid distance
+    pub fn predict_proba(&self, data: &DMat<f64>) -> DMat<f64> {
+        return self.proba(self.neighbors.nearest(data, self.k));
+    }
+
+    /// probability of each class using the specified metric
+    pub fn predict_proba_with<M: Metric>(&self, data: &DMat<f64>, metric: M) -> DMat<f64> {
+        return self.proba(self.neighbors.nearest_with(data, self.k, &metric));
+    }
+
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<usize> {
+        return self.most_probable(&self.predict_proba(data));
+    }
+
+    pub fn predict_with<M: Metric>(&self, data: &DMat<f64>, metric: M) -> DVec<usize> {
+        return self.most_probable(&self.predict_proba_with(data, metric));
+    }
+
+    fn proba(&self, found: Vec<Vec<(usize, f64)>>) -> DMat<f64> {
+        let mut proba = DMat::from_elem(found.len(), self.classes.len(), 0.);
+        for (r, neighbors) in found.iter().enumerate() {
+            let w = neighbor_weights(neighbors, &self.weights);
+            for (&(i, _), wi) in neighbors.iter().zip(w) {
+                proba[(r, self.y[i])] += wi;
+            }
+        }
+        return proba;
+    }
+
+    /// class with the highest probability, the smaller label is chosen for ties
+    fn most_probable(&self, proba: &DMat<f64>) -> DVec<usize> {
+        return DVec::from_fn(proba.nrows(), |r| {
+            let mut tmp_c = 0;
+            for c in 1..proba.ncols() {
+                if proba[(r, c)] > proba[(r, tmp_c)] {
+                    tmp_c = c;
+                }
+            }
+            self.classes[tmp_c]
+        });
+    }
+}
+
+/// k-nearest neighbors regressor
+pub struct KNeighborsRegressor {
+    pub k: usize,
+    pub weights: KNNWeights,
+    neighbors: Neighbors,
+    y: Vec<f64>,
+}
+
+impl KNeighborsRegressor {
+
+    pub fn new(k: usize) -> KNeighborsRegressor {
+        assert!(k > 0, "k must be 1 or more");
+        KNeighborsRegressor {
+            k: k,
+            weights: KNNWeights::Uniform,
+            neighbors: Neighbors::new(),
+            y: vec![],
+        }
+    }
+
+    pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<f64>) {
+        assert!(data.nrows() == y.len(), "Data and targets must have the same length");
+        self.y = y.at.clone();
+        self.neighbors.fit(data);
+    }
+
+    /// row numbers and Euclid distances of the k nearest training records
+    pub fn kneighbors(&self, data: &DMat<f64>, k: usize) -> (DMat<usize>, DMat<f64>) {
+        return to_matrices(self.neighbors.nearest(data, k), k);
+    }
+
+    /// row numbers and distances of the k nearest training records using the specified metric
+    pub fn kneighbors_with<M: Metric>(&self, data: &DMat<f64>, k: usize,
+                                      metric: M) -> (DMat<usize>, DMat<f64>) {
+        return to_matrices(self.neighbors.nearest_with(data, k, &metric), k);
+    }
+
+    /// weighted mean of the targets of neighbors using Euclid distance
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<f64> {
+        return self.weighted_mean(self.neighbors.nearest(data, self.k));
+    }
+
+    /// weighted mean of the targets of neighbors using the specified metric
+    pub fn predict_with<M: Metric>(&self, data: &DMat<f64>, metric: M) -> DVec<f64> {
+        return self.weighted_mean(self.neighbors.nearest_with(data, self.k, &metric));
+    }
+
+    fn weighted_mean(&self, found: Vec<Vec<(usize, f64)>>) -> DVec<f64> {
+        return DVec::from_fn(found.len(), |r| {
+            let w = neighbor_weights(&found[r], &self.weights);
+            found[r].iter().zip(w).fold(0., |a, (&(i, _), wi)| a + self.y[i] * wi)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{KNeighborsClassifier, KNeighborsRegressor, KNNWeights};
+    use super::super::mathfunc::distance::{Euclidean, Manhattan};
+
+    #[test]
+    fn test_knn_classifier() {
+        let data: DMat<f64> = DMat::from_row_vec(6, 2, &vec![0., 0., 1., 0., 0., 1.,
+                                                             5., 5., 6., 5., 5., 6.]);
+        let y: DVec<usize> = DVec::from_slice(6, &vec![3, 3, 3, 7, 7, 7]);
+
+        let mut knn = KNeighborsClassifier::new(3);
+        knn.fit(&data, &y);
+        assert_eq!(knn.classes, vec![3, 7]);
+
+        let test: DMat<f64> = DMat::from_row_vec(3, 2, &vec![0.5, 0.5, 5.5, 5.5, 2., 2.]);
+        let expected: DVec<usize> = DVec::from_slice(3, &vec![3, 7, 3]);
+        assert_eq!(knn.predict(&test), expected);
+        assert_eq!(knn.predict_with(&test, Manhattan), expected);
+
+        let proba = knn.predict_proba(&test);
+        assert_eq!(proba.nrows(), 3);
+        assert_eq!(proba.ncols(), 2);
+        assert_eq!(proba[(0, 0)], 1.);
+        assert_eq!(proba[(1, 1)], 1.);
+
+        // (2, 2) has 3 neighbors (1, 0), (0, 1) and (0, 0) at distance sqrt(5), sqrt(5), sqrt(8)
+        let (indices, distances) = knn.kneighbors(&test, 2);
+        assert_eq!(indices.ncols(), 2);
+        assert_eq!(indices[(2, 0)], 1);
+        assert_eq!(indices[(2, 1)], 2);
+        assert_eq!(distances[(2, 0)], 5f64.sqrt());
+        let (indices_with, distances_with) = knn.kneighbors_with(&test, 2, Euclidean);
+        assert_eq!(indices_with, indices);
+        assert_eq!(distances_with, distances);
+
+        // 5 neighbors include 2 records of 7
+        let mut knn = KNeighborsClassifier::new(5);
+        knn.fit(&data, &y);
+        let proba = knn.predict_proba(&test);
+        assert!((proba[(2, 0)] - 0.6).abs() < 1e-10);
+        assert!((proba[(2, 1)] - 0.4).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_knn_distance_weights() {
+        let data: DMat<f64> = DMat::from_row_vec(3, 1, &vec![0., 1., 4.]);
+        let y: DVec<usize> = DVec::from_slice(3, &vec![0, 1, 1]);
+        let test: DMat<f64> = DMat::from_row_vec(2, 1, &vec![0.5, 4.]);
+
+        // uniform weights choose the majority
+        let mut knn = KNeighborsClassifier::new(3);
+        knn.fit(&data, &y);
+        assert_eq!(knn.predict(&test), DVec::from_slice(2, &vec![1, 1]));
+
+        // weights are 2, 2, 1 / 3.5 for 0.5
+        knn.weights = KNNWeights::Distance;
+        let proba = knn.predict_proba(&test);
+        let total = 4. + 1. / 3.5;
+        assert!((proba[(0, 0)] - 2. / total).abs() < 1e-10);
+        // the record at distance 0 has the whole weight
+        assert_eq!(proba[(1, 1)], 1.);
+
+        let yr: DVec<f64> = DVec::from_slice(3, &vec![0., 1., 4.]);
+        let mut knn = KNeighborsRegressor::new(3);
+        knn.fit(&data, &yr);
+        let predicted = knn.predict(&test);
+        assert!((predicted[0] - 5. / 3.).abs() < 1e-10);
+        knn.weights = KNNWeights::Distance;
+        let predicted = knn.predict(&test);
+        assert!((predicted[0] - (2. + 4. / 3.5) / total).abs() < 1e-10);
+        assert_eq!(predicted[1], 4.);
+    }
+
+    #[test]
+    fn test_knn_regressor() {
+        let data: DMat<f64> = DMat::from_fn(20, 1, |i, _| i as f64);
+        let y: DVec<f64> = DVec::from_fn(20, |i| (i * 2) as f64);
+
+        let mut knn = KNeighborsRegressor::new(2);
+        knn.fit(&data, &y);
+        let test: DMat<f64> = DMat::from_row_vec(2, 1, &vec![3.5, 10.4]);
+        let predicted = knn.predict(&test);
+        assert_eq!(predicted[0], 7.);
+        assert_eq!(predicted[1], 21.);
+        assert_eq!(knn.predict_with(&test, |a: &DVec<f64>, b: &DVec<f64>| (a[0] - b[0]).abs()),
+                   predicted);
+
+        let (indices, _) = knn.kneighbors(&test, 3);
+        assert_eq!(indices[(1, 0)], 10);
+        assert_eq!(indices[(1, 1)], 11);
+        assert_eq!(indices[(1, 2)], 9);
+    }
+}
@@ -2,13 +2,25 @@ extern crate csv;
 extern crate nalgebra;
 extern crate num;
 extern crate rand;
+#[cfg(feature = "plot")]
+extern crate gnuplot;
 
 pub mod io;
 pub mod mathfunc;
 
 // single files
+pub mod dbscan;
+pub mod gmm;
 pub mod hclust;
+pub mod kdtree;
 pub mod kmeans;
+pub mod kmedoids;
+pub mod knn;
+pub mod linear;
 pub mod lm;
+pub mod metrics;
 pub mod pca;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod sparse;
 pub mod svm;
@@ -0,0 +1,319 @@
+extern crate nalgebra;
+extern crate rand;
+
+use nalgebra::{DVec, DMat};
+use rand::Rng;
+use std::f64;
+
+use super::mathfunc::get_rng;
+use super::sparse::Rows;
+
+/// Loss function of LinearSVC
+pub enum LinearLoss {
+    Hinge,                  // L1-loss
+    SquaredHinge,           // L2-loss
+}
+
+/// Linear SVM trained by dual coordinate descent (same as LIBLINEAR)
+///
+/// The intercept is handled as an additional feature which is always `bias`,
+/// thus it is regularized as well as other weights. `b` is the intercept, i.e. the
+/// weight of the additional feature multiplied by `bias`.
+pub struct LinearSVC {
+    c: f64,
+    tolerance: f64,
+    max_iter: usize,
+
+    pub loss: LinearLoss,
+    pub bias: f64,
+    pub seed: Option<usize>,
+    pub w: DVec<f64>,
+    pub b: f64,                     // intercept
+}
+
+impl LinearSVC {
+
+    pub fn new(c: f64, tolerance: f64, max_iter: usize) -> LinearSVC {
+        LinearSVC {
+            c: c,
+            tolerance: tolerance,
+            max_iter: max_iter,
+
+            loss: LinearLoss::Hinge,
+            bias: 1.,
+            seed: None,
+            w: DVec::from_elem(1, 0.),
+            b: 0.,
+        }
+    }
+
+    /// y must be -1 or 1
+    pub fn fit<M: Rows>(&mut self, data: &M, y: &DVec<f64>) {
+        let n = data.nrows();
+        assert!(n == y.len());
+
+        let (diag, upper) = match self.loss {
+            LinearLoss::Hinge => (0., self.c),
+            LinearLoss::SquaredHinge => (0.5 / self.c, f64::INFINITY)
+        };
+
+        // weight of the bias feature
+        self.w = DVec::from_elem(data.ncols(), 0.);
+        let mut wb = 0.;
+        let qd = DVec::from_fn(n, |i| diag + data.row_sqnorm(i) + self.bias * self.bias);
+        let mut alpha = DVec::from_elem(n, 0.);
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut rng = get_rng(self.seed);
+
+        for _ in 0..self.max_iter {
+            rng.shuffle(&mut perm);
+
+            let mut pgmax = f64::NEG_INFINITY;
+            let mut pgmin = f64::INFINITY;
+
+            for &i in &perm {
+                let g = y[i] * (data.row_dot(i, &self.w) + wb * self.bias)
+                        - 1. + diag * alpha[i];
+
+                // projected gradient
+                let pg = if alpha[i] == 0. {
+                    g.min(0.)
+                } else if alpha[i] == upper {
+                    g.max(0.)
+                } else {
+                    g
+                };
+                pgmax = pgmax.max(pg);
+                pgmin = pgmin.min(pg);
+
+                if pg.abs() > 1e-12 {
+                    let old = alpha[i];
+                    alpha[i] = (old - g / qd[i]).max(0.).min(upper);
+                    let d = (alpha[i] - old) * y[i];
+                    data.row_axpy(i, d, &mut self.w);
+                    wb += d * self.bias;
+                }
+            }
+            if pgmax - pgmin < self.tolerance {
+                break;
+            }
+        }
+        self.b = wb * self.bias;
+    }
+
+    pub fn decision_function<M: Rows>(&self, data: &M) -> DVec<f64> {
+        return DVec::from_fn(data.nrows(), |i| data.row_dot(i, &self.w) + self.b);
+    }
+
+    /// predicted labels (-1 or 1)
+    pub fn predict<M: Rows>(&self, data: &M) -> DVec<f64> {
+        let dec_values = self.decision_function(data);
+        return DVec::from_fn(dec_values.len(), |i| if dec_values[i] > 0. { 1. } else { -1. });
+    }
+}
+
+/// L2-regularized logistic regression trained by dual coordinate descent
+/// (Yu, Huang and Lin, same as LIBLINEAR)
+pub struct LogisticRegression {
+    c: f64,
+    tolerance: f64,
+    max_iter: usize,
+
+    pub bias: f64,
+    pub seed: Option<usize>,
+    pub w: DVec<f64>,
+    pub b: f64,                     // intercept
+}
+
+impl LogisticRegression {
+
+    pub fn new(c: f64, tolerance: f64, max_iter: usize) -> LogisticRegression {
+        LogisticRegression {
+            c: c,
+            tolerance: tolerance,
+            max_iter: max_iter,
+
+            bias: 1.,
+            seed: None,
+            w: DVec::from_elem(1, 0.),
+            b: 0.,
+        }
+    }
+
+    /// y must be -1 or 1
+    pub fn fit<M: Rows>(&mut self, data: &M, y: &DVec<f64>) {
+        let n = data.nrows();
+        assert!(n == y.len());
+
+        let c = self.c;
+        let max_inner_iter = 100;
+        let inner_eps = 1e-2;
+
+        // alpha[2i] + alpha[2i + 1] = C
+        let mut alpha = DVec::from_fn(2 * n, |i| {
+            let a = (0.001 * c).min(1e-8);
+            if i % 2 == 0 { a } else { c - a }
+        });
+        // weight of the bias feature
+        self.w = DVec::from_elem(data.ncols(), 0.);
+        let mut wb = 0.;
+        for i in 0..n {
+            data.row_axpy(i, y[i] * alpha[2 * i], &mut self.w);
+            wb += y[i] * alpha[2 * i] * self.bias;
+        }
+
+        let xtx = DVec::from_fn(n, |i| data.row_sqnorm(i) + self.bias * self.bias);
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut rng = get_rng(self.seed);
+
+        for _ in 0..self.max_iter {
+            rng.shuffle(&mut perm);
+            let mut gmax: f64 = 0.;
+
+            for &i in &perm {
+                let a = xtx[i];
+                let b = y[i] * (data.row_dot(i, &self.w) + wb * self.bias);
+
+                // decide which of the pair to update
+                let (ind1, ind2, sign) = if 0.5 * a * (alpha[2 * i + 1] - alpha[2 * i]) + b < 0. {
+                    (2 * i + 1, 2 * i, -1.)
+                } else {
+                    (2 * i, 2 * i + 1, 1.)
+                };
+
+                // solve the one-variable sub-problem by Newton method
+                let alpha_old = alpha[ind1];
+                let mut z = alpha_old;
+                if c - z < 0.5 * c {
+                    z = 0.1 * z;
+                }
+                let mut gp = a * (z - alpha_old) + sign * b + (z / (c - z)).ln();
+                gmax = gmax.max(gp.abs());
+
+                let mut inner_iter = 0;
+                while inner_iter <= max_inner_iter {
+                    if gp.abs() < inner_eps {
+                        break;
+                    }
+                    let gpp = a + c / (c - z) / z;
+                    let tmpz = z - gp / gpp;
+                    if tmpz <= 0. {
+                        z = z * 0.1;
+                    } else {
+                        z = tmpz;
+                    }
+                    gp = a * (z - alpha_old) + sign * b + (z / (c - z)).ln();
+                    inner_iter += 1;
+                }
+
+                if inner_iter > 0 {
+                    alpha[ind1] = z;
+                    alpha[ind2] = c - z;
+                    let d = sign * (z - alpha_old) * y[i];
+                    data.row_axpy(i, d, &mut self.w);
+                    wb += d * self.bias;
+                }
+            }
+            if gmax < self.tolerance {
+                break;
+            }
+        }
+        self.b = wb * self.bias;
+    }
+
+    pub fn decision_function<M: Rows>(&self, data: &M) -> DVec<f64> {
+        return DVec::from_fn(data.nrows(), |i| data.row_dot(i, &self.w) + self.b);
+    }
+
+    /// predicted labels (-1 or 1)
+    pub fn predict<M: Rows>(&self, data: &M) -> DVec<f64> {
+        let dec_values = self.decision_function(data);
+        return DVec::from_fn(dec_values.len(), |i| if dec_values[i] > 0. { 1. } else { -1. });
+    }
+
+    /// probabilities of labels, columns correspond to -1 and 1
+    pub fn predict_proba<M: Rows>(&self, data: &M) -> DMat<f64> {
+        let dec_values = self.decision_function(data);
+        return DMat::from_fn(data.nrows(), 2, |i, j| {
+            let p = 1. / (1. + (- dec_values[i]).exp());
+            if j == 1 { p } else { 1. - p }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{LinearSVC, LinearLoss, LogisticRegression};
+    use super::super::sparse::CsrMat;
+
+    fn get_data() -> (DMat<f64>, DVec<f64>) {
+        // separable by x0 + x1 = 5
+        let x: DMat<f64> = DMat::from_row_vec(8, 2, &vec![0., 1., 1., 2., 2., 0., 1., 1.,
+                                                          4., 5., 5., 3., 3., 6., 6., 6.]);
+        let y: DVec<f64> = DVec::from_slice(8, &vec![-1., -1., -1., -1., 1., 1., 1., 1.]);
+        return (x, y);
+    }
+
+    #[test]
+    fn test_linear_svc() {
+        let (x, y) = get_data();
+
+        let mut svc = LinearSVC::new(10., 0.001, 1000);
+        svc.seed = Some(1);
+        svc.fit(&x, &y);
+        assert_eq!(svc.predict(&x), y);
+
+        let mut svc = LinearSVC::new(10., 0.001, 1000);
+        svc.loss = LinearLoss::SquaredHinge;
+        svc.seed = Some(1);
+        svc.fit(&CsrMat::from_dmat(&x), &y);
+        assert_eq!(svc.predict(&x), y);
+    }
+
+    #[test]
+    fn test_logistic_regression() {
+        let (x, y) = get_data();
+
+        let mut lr = LogisticRegression::new(10., 0.001, 1000);
+        lr.seed = Some(1);
+        lr.fit(&CsrMat::from_dmat(&x), &y);
+        assert_eq!(lr.predict(&x), y);
+
+        let proba = lr.predict_proba(&x);
+        for i in 0..y.len() {
+            assert!((proba[(i, 0)] + proba[(i, 1)] - 1.).abs() < 1e-10);
+            assert_eq!(proba[(i, 1)] > 0.5, y[i] == 1.);
+        }
+    }
+
+    #[test]
+    fn test_intercept() {
+        let (x, y) = get_data();
+
+        // decision values are w x + b regardless of bias
+        let check = |w: &DVec<f64>, b: f64, dec_values: &DVec<f64>| {
+            for i in 0..x.nrows() {
+                let v = w[0] * x[(i, 0)] + w[1] * x[(i, 1)] + b;
+                assert!((dec_values[i] - v).abs() < 1e-10);
+            }
+        };
+
+        let mut svc = LinearSVC::new(10., 0.001, 1000);
+        svc.bias = 10.;
+        svc.seed = Some(1);
+        svc.fit(&x, &y);
+        assert_eq!(svc.predict(&x), y);
+        check(&svc.w, svc.b, &svc.decision_function(&x));
+        // the boundary x0 + x1 = 5 needs a large intercept
+        assert!(svc.b < -1.);
+
+        let mut lr = LogisticRegression::new(10., 0.001, 1000);
+        lr.bias = 10.;
+        lr.seed = Some(1);
+        lr.fit(&x, &y);
+        assert_eq!(lr.predict(&x), y);
+        check(&lr.w, lr.b, &lr.decision_function(&x));
+        assert!(lr.b < -1.);
+    }
+}
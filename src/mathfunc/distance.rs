@@ -0,0 +1,275 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, Inv, Mean, RowSlice};
+
+use super::{euc_dist, inner_product};
+
+/// Distance between 2 records
+///
+/// Functions and closures taking 2 records are also regarded as metrics.
+pub trait Metric {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64;
+}
+
+impl<F> Metric for F where F: Fn(&DVec<f64>, &DVec<f64>) -> f64 {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        return self(x1, x2);
+    }
+}
+
+/// Euclid distance (same as mathfunc::euc_dist)
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        return euc_dist(x1, x2);
+    }
+}
+
+/// sum of absolute differences
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        return (0..x1.len()).fold(0., |a, i| a + (x1[i] - x2[i]).abs());
+    }
+}
+
+/// maximum of absolute differences
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        return (0..x1.len()).fold(0., |a: f64, i| a.max((x1[i] - x2[i]).abs()));
+    }
+}
+
+/// Minkowski distance of order p
+pub struct Minkowski {
+    p: f64
+}
+
+impl Minkowski {
+
+    pub fn new(p: f64) -> Minkowski {
+        assert!(p >= 1., "p must be 1 or more");
+        Minkowski {
+            p: p
+        }
+    }
+}
+
+impl Metric for Minkowski {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        let total = (0..x1.len()).fold(0., |a, i| a + (x1[i] - x2[i]).abs().powf(self.p));
+        return total.powf(1. / self.p);
+    }
+}
+
+/// 1 - cosine similarity
+///
+/// Zero vector has distance 1 to other vectors and 0 to itself (same as scikit-learn).
+pub struct Cosine;
+
+impl Metric for Cosine {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        let (norm1, norm2) = (inner_product(x1, x1), inner_product(x2, x2));
+        if norm1 == 0. || norm2 == 0. {
+            return if norm1 == norm2 { 0. } else { 1. };
+        }
+        return 1. - inner_product(x1, x2) / (norm1 * norm2).sqrt();
+    }
+}
+
+/// 1 - Pearson correlation coefficient
+///
+/// Constant records are handled as zero vectors of Cosine.
+pub struct Correlation;
+
+impl Metric for Correlation {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        let n = x1.len() as f64;
+        let m1 = x1.at.iter().fold(0., |a, v| a + v) / n;
+        let m2 = x2.at.iter().fold(0., |a, v| a + v) / n;
+        let c1 = DVec::from_fn(x1.len(), |i| x1[i] - m1);
+        let c2 = DVec::from_fn(x2.len(), |i| x2[i] - m2);
+        return Cosine.distance(&c1, &c2);
+    }
+}
+
+/// Mahalanobis distance
+pub struct Mahalanobis {
+    inv_cov: DMat<f64>                  // inverse of the covariance matrix
+}
+
+impl Mahalanobis {
+
+    /// create from the inverse of the covariance matrix
+    pub fn new(inv_cov: DMat<f64>) -> Mahalanobis {
+        assert!(inv_cov.nrows() == inv_cov.ncols(), "Inverse covariance matrix must be square");
+        Mahalanobis {
+            inv_cov: inv_cov
+        }
+    }
+
+    /// create from the sample covariance matrix of the data
+    pub fn from_data(data: &DMat<f64>) -> Mahalanobis {
+        let n = data.nrows();
+        assert!(n > 1, "Data must have 2 or more records");
+        let means: DVec<f64> = data.mean();
+        let cov = DMat::from_fn(data.ncols(), data.ncols(), |p, q| {
+            (0..n).fold(0., |a, i| a + (data[(i, p)] - means[p]) * (data[(i, q)] - means[q]))
+            / (n - 1) as f64
+        });
+        return match cov.inv() {
+            Some(inv_cov) => Mahalanobis::new(inv_cov),
+            None => panic!("Covariance matrix is singular")
+        };
+    }
+}
+
+impl Metric for Mahalanobis {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len() && x1.len() == self.inv_cov.nrows());
+        let d = x1.len();
+        let mut total = 0.;
+        for p in 0..d {
+            for q in 0..d {
+                total += (x1[p] - x2[p]) * self.inv_cov[(p, q)] * (x1[q] - x2[q]);
+            }
+        }
+        return total.max(0.).sqrt();
+    }
+}
+
+/// proportion of elements which differ
+pub struct Hamming;
+
+impl Metric for Hamming {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        let ndiff = (0..x1.len()).filter(|&i| x1[i] != x2[i]).count();
+        return ndiff as f64 / x1.len() as f64;
+    }
+}
+
+/// Jaccard distance regarding non-zero elements as true
+pub struct Jaccard;
+
+impl Metric for Jaccard {
+    fn distance(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        assert!(x1.len() == x2.len());
+        let union = (0..x1.len()).filter(|&i| x1[i] != 0. || x2[i] != 0.).count();
+        if union == 0 {
+            return 0.;
+        }
+        let intersection = (0..x1.len()).filter(|&i| x1[i] != 0. && x2[i] != 0.).count();
+        return 1. - intersection as f64 / union as f64;
+    }
+}
+
+/// distances between each row of x (rows) and each row of y (columns)
+pub fn pairwise_distances<M: Metric>(x: &DMat<f64>, y: &DMat<f64>, metric: &M) -> DMat<f64> {
+    assert!(x.ncols() == y.ncols());
+    let xrows: Vec<DVec<f64>> = (0..x.nrows()).map(|r| x.row_slice(r, 0, x.ncols())).collect();
+    let yrows: Vec<DVec<f64>> = (0..y.nrows()).map(|r| y.row_slice(r, 0, y.ncols())).collect();
+    return DMat::from_fn(x.nrows(), y.nrows(), |i, j| metric.distance(&xrows[i], &yrows[j]));
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{Metric, Euclidean, Manhattan, Chebyshev, Minkowski, Cosine, Correlation,
+                Mahalanobis, Hamming, Jaccard, pairwise_distances};
+
+    #[test]
+    fn test_metrics() {
+        let v1: DVec<f64> = DVec::from_slice(3, &vec![3., 4., 5.]);
+        let v2: DVec<f64> = DVec::from_slice(3, &vec![7., 8., 2.]);
+
+        assert_eq!(Euclidean.distance(&v1, &v2), 6.4031242374328485);
+        assert_eq!(Manhattan.distance(&v1, &v2), 11.);
+        assert_eq!(Chebyshev.distance(&v1, &v2), 4.);
+        assert_eq!(Minkowski::new(1.).distance(&v1, &v2), 11.);
+        assert!((Minkowski::new(2.).distance(&v1, &v2) - 6.4031242374328485).abs() < 1e-10);
+        assert!((Minkowski::new(3.).distance(&v1, &v2) - 155f64.powf(1. / 3.)).abs() < 1e-10);
+
+        // 1 - 63 / sqrt(50 * 117)
+        assert!((Cosine.distance(&v1, &v2) - (1. - 63. / 5850f64.sqrt())).abs() < 1e-10);
+        let v3: DVec<f64> = DVec::from_slice(3, &vec![6., 8., 10.]);
+        assert!(Cosine.distance(&v1, &v3).abs() < 1e-10);
+        assert!(Correlation.distance(&v1, &v3).abs() < 1e-10);
+        let v4: DVec<f64> = DVec::from_slice(3, &vec![5., 4., 3.]);
+        assert!((Correlation.distance(&v1, &v4) - 2.).abs() < 1e-10);
+
+        // identity matrix gives Euclid distance
+        let identity = DMat::from_fn(3, 3, |i, j| if i == j { 1. } else { 0. });
+        let mahalanobis = Mahalanobis::new(identity);
+        assert!((mahalanobis.distance(&v1, &v2) - 6.4031242374328485).abs() < 1e-10);
+
+        let b1: DVec<f64> = DVec::from_slice(4, &vec![1., 0., 1., 1.]);
+        let b2: DVec<f64> = DVec::from_slice(4, &vec![1., 1., 0., 1.]);
+        assert_eq!(Hamming.distance(&b1, &b2), 0.5);
+        assert_eq!(Jaccard.distance(&b1, &b2), 0.5);
+        let zeros: DVec<f64> = DVec::from_elem(4, 0.);
+        assert_eq!(Jaccard.distance(&zeros, &zeros), 0.);
+
+        // closure as a metric
+        let doubled = |a: &DVec<f64>, b: &DVec<f64>| Manhattan.distance(a, b) * 2.;
+        assert_eq!(doubled.distance(&v1, &v2), 22.);
+    }
+
+    #[test]
+    fn test_cosine_zero_vector() {
+        let v: DVec<f64> = DVec::from_slice(2, &vec![3., 4.]);
+        let zeros: DVec<f64> = DVec::from_elem(2, 0.);
+        assert_eq!(Cosine.distance(&v, &zeros), 1.);
+        assert_eq!(Cosine.distance(&zeros, &v), 1.);
+        assert_eq!(Cosine.distance(&zeros, &zeros), 0.);
+
+        // constant records
+        let c1: DVec<f64> = DVec::from_elem(2, 1.);
+        let c2: DVec<f64> = DVec::from_elem(2, 5.);
+        assert_eq!(Correlation.distance(&c1, &c2), 0.);
+        assert_eq!(Correlation.distance(&c1, &v), 1.);
+    }
+
+    #[test]
+    #[should_panic(expected = "Data must have 2 or more records")]
+    fn test_mahalanobis_one_record() {
+        let data: DMat<f64> = DMat::from_row_vec(1, 2, &vec![1., 2.]);
+        Mahalanobis::from_data(&data);
+    }
+
+    #[test]
+    fn test_mahalanobis_from_data() {
+        // columns are not correlated
+        let data: DMat<f64> = DMat::from_row_vec(4, 2, &vec![-1., -2., -1., 2., 1., -2., 1., 2.]);
+        let mahalanobis = Mahalanobis::from_data(&data);
+        let v1: DVec<f64> = DVec::from_slice(2, &vec![0., 0.]);
+        let v2: DVec<f64> = DVec::from_slice(2, &vec![0., 4.]);
+        // sample variance of the 2nd column is 16 / 3
+        assert!((mahalanobis.distance(&v1, &v2) - (16f64 / (16. / 3.)).sqrt()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_pairwise_distances() {
+        let x: DMat<f64> = DMat::from_row_vec(3, 2, &vec![0., 0., 3., 4., 1., 1.]);
+        let y: DMat<f64> = DMat::from_row_vec(2, 2, &vec![0., 0., 3., 0.]);
+
+        let dist = pairwise_distances(&x, &y, &Euclidean);
+        assert_eq!(dist.nrows(), 3);
+        assert_eq!(dist.ncols(), 2);
+        assert_eq!(dist[(1, 0)], 5.);
+        assert_eq!(dist[(1, 1)], 4.);
+
+        let dist = pairwise_distances(&x, &x, &Manhattan);
+        assert_eq!(dist[(0, 1)], 7.);
+        assert_eq!(dist[(1, 0)], 7.);
+        assert_eq!(dist[(2, 2)], 0.);
+    }
+}
@@ -1,11 +1,15 @@
 extern crate nalgebra;
 extern crate num;
+extern crate rand;
 
 use nalgebra::{DVec, DMat, Iterable, ColSlice};
 use num::{Num, Zero, Float, Signed};
+use rand::{StdRng, SeedableRng};
 use std::ops::Sub;
 use std::vec::Vec;
 
+pub mod distance;
+
 /// Get minimum values of each column of DVec
 pub fn dvec_min<T: Float>(data: &DVec<T>) -> T {
     // can't use normal min(a, b), because it can't handle NaN
@@ -48,6 +52,17 @@ pub fn dmat_copy<T: Copy>(data: &DMat<T>) -> DMat<T> {
     return DMat::from_col_vec(data.nrows(), data.ncols(), &values);
 }
 
+/// Take rows of DMat by indices
+pub fn dmat_take_rows<T: Copy>(data: &DMat<T>, indices: &[usize]) -> DMat<T> {
+    return DMat::from_fn(indices.len(), data.ncols(), |i, j| data[(indices[i], j)]);
+}
+
+/// Take elements of DVec by indices
+pub fn dvec_take<T: Copy>(data: &DVec<T>, indices: &[usize]) -> DVec<T> {
+    let values: Vec<T> = indices.iter().map(|&i| data[i]).collect();
+    return DVec::from_slice(indices.len(), &values);
+}
+
 /// Sum of squares
 pub fn sum_square<T: Num + Zero + Sub + Copy>(vec1: &DVec<T>, vec2: &DVec<T>, m1: T, m2: T) -> T {
     let mut val: T = Zero::zero();
@@ -77,6 +92,14 @@ pub fn inner_product<T: Float + Signed>(vec1: &DVec<T>, vec2: &DVec<T>) -> T {
     return val;
 }
 
+/// Random number generator, seeded when seed is specified
+pub fn get_rng(seed: Option<usize>) -> StdRng {
+    return match seed {
+        Some(s) => StdRng::from_seed(&[s][..]),
+        None => StdRng::new().unwrap()
+    };
+}
+
 /// Round DMat elements to specified decimals
 pub fn round(data: &DMat<f64>, decimals: usize) -> DMat<f64> {
     let nrows = data.nrows();
@@ -92,8 +115,9 @@ pub fn round(data: &DMat<f64>, decimals: usize) -> DMat<f64> {
 #[cfg(test)]
 mod tests {
     use nalgebra::{DVec, DMat};
-    use super::{dvec_min, dvec_max, dmat_min, dmat_max,
-                sum_square, euc_dist, inner_product, round};
+    use super::{dvec_min, dvec_max, dmat_min, dmat_max, dmat_take_rows, dvec_take,
+                sum_square, euc_dist, inner_product, round, get_rng};
+    use rand::Rng;
 
     #[test]
     fn test_dvec_minmax_float() {
@@ -119,6 +143,17 @@ mod tests {
         assert_eq!(exp, dmat_max(&m));
     }
 
+    #[test]
+    fn test_take() {
+        let v: DVec<f64> = DVec::from_slice(3, &vec![3., 1., 4.]);
+        let exp: DVec<f64> = DVec::from_slice(2, &vec![4., 3.]);
+        assert_eq!(exp, dvec_take(&v, &[2, 0]));
+
+        let m: DMat<f64> = DMat::from_row_vec(3, 2, &vec![3., 1., 4., 2., 5., 6.]);
+        let exp: DMat<f64> = DMat::from_row_vec(2, 2, &vec![5., 6., 3., 1.]);
+        assert_eq!(exp, dmat_take_rows(&m, &[2, 0]));
+    }
+
     #[test]
     fn test_sum_square() {
         let val1: Vec<f64> = vec![3., 4., 5.];
@@ -157,6 +192,16 @@ mod tests {
         assert_eq!(19.0, inner_product(&v1, &v2));
     }
 
+    #[test]
+    fn test_get_rng() {
+        let v1: Vec<f64> = get_rng(Some(1)).gen_iter().take(5).collect();
+        let v2: Vec<f64> = get_rng(Some(1)).gen_iter().take(5).collect();
+        assert_eq!(v1, v2);
+
+        let v3: Vec<f64> = get_rng(Some(2)).gen_iter().take(5).collect();
+        assert!(v1 != v3);
+    }
+
     #[test]
     fn test_dmat_round() {
         let m: DMat<f64> = DMat::from_row_vec(2, 2, &vec![5.555, 1.111, 4.444, 2.222]);
@@ -0,0 +1,402 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat, RowSlice, Iterable};
+use std::cmp;
+use std::f64;
+
+use super::mathfunc::{euc_dist};
+
+/// Clusters found in labels, with their centroids
+struct Clusters {
+    labels: Vec<usize>,         // sorted unique labels
+    indexer: Vec<usize>,        // position of each record's label in labels
+    counts: Vec<usize>,
+    centroids: Vec<DVec<f64>>
+}
+
+impl Clusters {
+
+    fn new(data: &DMat<f64>, labels: &DVec<usize>) -> Clusters {
+        assert!(data.nrows() == labels.len());
+
+        let mut unique: Vec<usize> = labels.iter().cloned().collect();
+        unique.sort();
+        unique.dedup();
+
+        let indexer: Vec<usize> = labels.iter()
+                                        .map(|l| unique.binary_search(l).unwrap())
+                                        .collect();
+
+        let mut counts = vec![0; unique.len()];
+        let mut centroids = vec![DVec::from_elem(data.ncols(), 0.); unique.len()];
+        for (rownum, &c) in indexer.iter().enumerate() {
+            for j in 0..data.ncols() {
+                centroids[c][j] += data[(rownum, j)];
+            }
+            counts[c] += 1;
+        }
+        for c in 0..unique.len() {
+            centroids[c] = centroids[c].clone() / (counts[c] as f64);
+        }
+
+        Clusters {
+            labels: unique,
+            indexer: indexer,
+            counts: counts,
+            centroids: centroids
+        }
+    }
+
+    fn nclusters(&self) -> usize {
+        return self.labels.len();
+    }
+}
+
+/// Silhouette coefficient of each record
+///
+/// (b - a) / max(a, b), where a is the mean distance to other records in the same cluster
+/// and b is the mean distance to records in the nearest other cluster.
+/// Records in singleton clusters have 0.
+pub fn silhouette_samples(data: &DMat<f64>, labels: &DVec<usize>) -> DVec<f64> {
+    let clusters = Clusters::new(data, labels);
+    let k = clusters.nclusters();
+    assert!(k > 1, "Number of clusters must be larger than 1");
+
+    let nrows = data.nrows();
+    let rows: Vec<DVec<f64>> = (0..nrows).map(|r| data.row_slice(r, 0, data.ncols()))
+                                         .collect();
+
+    return DVec::from_fn(nrows, |i| {
+        let ci = clusters.indexer[i];
+        if clusters.counts[ci] == 1 {
+            return 0.;
+        }
+
+        // sum of distances to each cluster
+        let mut sums = vec![0.; k];
+        for j in 0..nrows {
+            if i != j {
+                sums[clusters.indexer[j]] += euc_dist(&rows[i], &rows[j]);
+            }
+        }
+        let a = sums[ci] / ((clusters.counts[ci] - 1) as f64);
+        let b = (0..k).filter(|&c| c != ci)
+                      .fold(f64::MAX, |m, c| m.min(sums[c] / (clusters.counts[c] as f64)));
+        (b - a) / a.max(b)
+    });
+}
+
+/// Mean silhouette coefficient of all records
+pub fn silhouette_score(data: &DMat<f64>, labels: &DVec<usize>) -> f64 {
+    let samples = silhouette_samples(data, labels);
+    return samples.iter().fold(0., |a, b| a + b) / (samples.len() as f64);
+}
+
+/// Calinski-Harabasz index (ratio of between- and within-cluster dispersion)
+pub fn calinski_harabasz_score(data: &DMat<f64>, labels: &DVec<usize>) -> f64 {
+    let clusters = Clusters::new(data, labels);
+    let k = clusters.nclusters();
+    let nrows = data.nrows();
+    assert!(k > 1 && k < nrows, "Number of clusters must be between 2 and nrows - 1");
+
+    let mean = DVec::from_fn(data.ncols(),
+                             |j| (0..nrows).fold(0., |a, r| a + data[(r, j)]) / (nrows as f64));
+
+    let mut between = 0.;
+    for c in 0..k {
+        let d = euc_dist(&clusters.centroids[c], &mean);
+        between += (clusters.counts[c] as f64) * d * d;
+    }
+    let within = inertia(data, labels);
+
+    if within == 0. {
+        return 1.;
+    }
+    return (between / ((k - 1) as f64)) / (within / ((nrows - k) as f64));
+}
+
+/// Davies-Bouldin index (mean similarity between each cluster and its most similar one)
+pub fn davies_bouldin_score(data: &DMat<f64>, labels: &DVec<usize>) -> f64 {
+    let clusters = Clusters::new(data, labels);
+    let k = clusters.nclusters();
+    assert!(k > 1, "Number of clusters must be larger than 1");
+
+    // mean distance between records and centroid
+    let mut scatters = vec![0.; k];
+    for (rownum, &c) in clusters.indexer.iter().enumerate() {
+        let row = data.row_slice(rownum, 0, data.ncols());
+        scatters[c] += euc_dist(&row, &clusters.centroids[c]);
+    }
+    for c in 0..k {
+        scatters[c] = scatters[c] / (clusters.counts[c] as f64);
+    }
+
+    let mut score = 0.;
+    for i in 0..k {
+        let mut max_ratio = 0.;
+        for j in 0..k {
+            if i != j {
+                let d = euc_dist(&clusters.centroids[i], &clusters.centroids[j]);
+                let ratio = if d == 0. { f64::INFINITY } else { (scatters[i] + scatters[j]) / d };
+                if ratio > max_ratio {
+                    max_ratio = ratio;
+                }
+            }
+        }
+        score += max_ratio;
+    }
+    return score / (k as f64);
+}
+
+/// Within-cluster sum of squares
+pub fn inertia(data: &DMat<f64>, labels: &DVec<usize>) -> f64 {
+    let clusters = Clusters::new(data, labels);
+    let mut val = 0.;
+    for (rownum, &c) in clusters.indexer.iter().enumerate() {
+        let row = data.row_slice(rownum, 0, data.ncols());
+        let d = euc_dist(&row, &clusters.centroids[c]);
+        val += d * d;
+    }
+    return val;
+}
+
+/// Contingency table, rows correspond to sorted labels of labels_true
+/// and columns to labels_pred
+pub fn contingency_matrix(labels_true: &DVec<usize>, labels_pred: &DVec<usize>) -> DMat<usize> {
+    assert!(labels_true.len() == labels_pred.len());
+
+    let classes = unique_labels(labels_true);
+    let clusters = unique_labels(labels_pred);
+
+    let mut table = DMat::from_elem(classes.len(), clusters.len(), 0);
+    for (t, p) in labels_true.iter().zip(labels_pred.iter()) {
+        let i = classes.binary_search(t).unwrap();
+        let j = clusters.binary_search(p).unwrap();
+        table[(i, j)] += 1;
+    }
+    return table;
+}
+
+/// Rand index adjusted for chance
+pub fn adjusted_rand_score(labels_true: &DVec<usize>, labels_pred: &DVec<usize>) -> f64 {
+    let table = contingency_matrix(labels_true, labels_pred);
+    let (rows, cols) = marginals(&table);
+
+    let comb2 = |n: usize| (n * n.saturating_sub(1)) as f64 / 2.;
+    let index = table.as_vec().iter().fold(0., |a, &n| a + comb2(n));
+    let sum_rows = rows.iter().fold(0., |a, &n| a + comb2(n));
+    let sum_cols = cols.iter().fold(0., |a, &n| a + comb2(n));
+
+    let expected = sum_rows * sum_cols / comb2(labels_true.len());
+    let max_index = (sum_rows + sum_cols) / 2.;
+    if max_index == expected {
+        // both labelings have only one cluster, or every record is its own cluster
+        return 1.;
+    }
+    return (index - expected) / (max_index - expected);
+}
+
+/// Mutual information between two labelings (natural logarithm)
+pub fn mutual_info_score(labels_true: &DVec<usize>, labels_pred: &DVec<usize>) -> f64 {
+    let table = contingency_matrix(labels_true, labels_pred);
+    let (rows, cols) = marginals(&table);
+    let n = labels_true.len() as f64;
+
+    let mut mi = 0.;
+    for i in 0..table.nrows() {
+        for j in 0..table.ncols() {
+            let nij = table[(i, j)] as f64;
+            if nij > 0. {
+                mi += nij / n * (n * nij / ((rows[i] * cols[j]) as f64)).ln();
+            }
+        }
+    }
+    return mi;
+}
+
+/// Mutual information normalized by the arithmetic mean of entropies
+pub fn normalized_mutual_info_score(labels_true: &DVec<usize>, labels_pred: &DVec<usize>) -> f64 {
+    let h_true = entropy(labels_true);
+    let h_pred = entropy(labels_pred);
+    if h_true == 0. && h_pred == 0. {
+        return 1.;
+    }
+    let mi = mutual_info_score(labels_true, labels_pred);
+    return mi / ((h_true + h_pred) / 2.);
+}
+
+/// Mutual information adjusted for chance (normalized by the arithmetic mean of entropies)
+pub fn adjusted_mutual_info_score(labels_true: &DVec<usize>, labels_pred: &DVec<usize>) -> f64 {
+    let h_true = entropy(labels_true);
+    let h_pred = entropy(labels_pred);
+    if h_true == 0. && h_pred == 0. {
+        return 1.;
+    }
+    let mi = mutual_info_score(labels_true, labels_pred);
+    let emi = expected_mutual_info(&contingency_matrix(labels_true, labels_pred));
+
+    let denominator = (h_true + h_pred) / 2. - emi;
+    if denominator == 0. {
+        return 1.;
+    }
+    return (mi - emi) / denominator;
+}
+
+/// Homogeneity, completeness and V-measure
+///
+/// Homogeneity is 1 when each cluster contains only members of a single class,
+/// completeness is 1 when all members of a class are assigned to the same cluster.
+pub fn homogeneity_completeness_v_measure(labels_true: &DVec<usize>,
+                                          labels_pred: &DVec<usize>) -> (f64, f64, f64) {
+    let h_true = entropy(labels_true);
+    let h_pred = entropy(labels_pred);
+    let mi = mutual_info_score(labels_true, labels_pred);
+
+    let homogeneity = if h_true == 0. { 1. } else { mi / h_true };
+    let completeness = if h_pred == 0. { 1. } else { mi / h_pred };
+    let v_measure = if homogeneity + completeness == 0. {
+        0.
+    } else {
+        2. * homogeneity * completeness / (homogeneity + completeness)
+    };
+    return (homogeneity, completeness, v_measure);
+}
+
+/// sorted unique labels
+fn unique_labels(labels: &DVec<usize>) -> Vec<usize> {
+    let mut unique: Vec<usize> = labels.iter().cloned().collect();
+    unique.sort();
+    unique.dedup();
+    return unique;
+}
+
+/// row and column sums of contingency table
+fn marginals(table: &DMat<usize>) -> (Vec<usize>, Vec<usize>) {
+    let rows = (0..table.nrows()).map(|i| (0..table.ncols()).fold(0, |a, j| a + table[(i, j)]))
+                                 .collect();
+    let cols = (0..table.ncols()).map(|j| (0..table.nrows()).fold(0, |a, i| a + table[(i, j)]))
+                                 .collect();
+    return (rows, cols);
+}
+
+/// entropy of labeling (natural logarithm)
+fn entropy(labels: &DVec<usize>) -> f64 {
+    let n = labels.len() as f64;
+    let mut counts: Vec<usize> = vec![];
+    for l in unique_labels(labels) {
+        counts.push(labels.iter().filter(|&v| *v == l).count());
+    }
+    return counts.iter().fold(0., |a, &c| {
+        let p = (c as f64) / n;
+        a - p * p.ln()
+    });
+}
+
+/// expected mutual information under the hypergeometric model of randomness
+fn expected_mutual_info(table: &DMat<usize>) -> f64 {
+    let (rows, cols) = marginals(table);
+    let n: usize = rows.iter().fold(0, |a, b| a + b);
+    let nf = n as f64;
+
+    // log(k!) for 0..n
+    let mut log_fact = vec![0.; n + 1];
+    for k in 1..(n + 1) {
+        log_fact[k] = log_fact[k - 1] + (k as f64).ln();
+    }
+
+    let mut emi = 0.;
+    for &a in &rows {
+        for &b in &cols {
+            let start = cmp::max(1, (a + b).saturating_sub(n));
+            let end = a.min(b);
+            for nij in start..(end + 1) {
+                let nijf = nij as f64;
+                let term1 = nijf / nf * (nf * nijf / ((a * b) as f64)).ln();
+                let log_term2 = log_fact[a] + log_fact[b] + log_fact[n - a] + log_fact[n - b]
+                                - log_fact[n] - log_fact[nij] - log_fact[a - nij]
+                                - log_fact[b - nij] - log_fact[n + nij - a - b];
+                emi += term1 * log_term2.exp();
+            }
+        }
+    }
+    return emi;
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{silhouette_samples, silhouette_score, calinski_harabasz_score,
+                davies_bouldin_score, inertia,
+                contingency_matrix, adjusted_rand_score, mutual_info_score,
+                normalized_mutual_info_score, adjusted_mutual_info_score,
+                homogeneity_completeness_v_measure, expected_mutual_info};
+
+    #[test]
+    fn test_internal_metrics() {
+        let data: DMat<f64> = DMat::from_row_vec(4, 1, &vec![0., 1., 10., 11.]);
+        // labels don't need to be contiguous
+        let labels: DVec<usize> = DVec::from_slice(4, &vec![2, 2, 5, 5]);
+
+        let exp = vec![9.5 / 10.5, 8.5 / 9.5, 8.5 / 9.5, 9.5 / 10.5];
+        let res = silhouette_samples(&data, &labels);
+        for i in 0..4 {
+            assert!((res[i] - exp[i]).abs() < 1e-10);
+        }
+        let mean = exp.iter().fold(0., |a, b| a + b) / 4.;
+        assert!((silhouette_score(&data, &labels) - mean).abs() < 1e-10);
+
+        assert_eq!(inertia(&data, &labels), 1.);
+        assert_eq!(calinski_harabasz_score(&data, &labels), 200.);
+        assert_eq!(davies_bouldin_score(&data, &labels), 0.1);
+
+        // singleton cluster
+        let labels: DVec<usize> = DVec::from_slice(4, &vec![0, 0, 0, 1]);
+        assert_eq!(silhouette_samples(&data, &labels)[3], 0.);
+    }
+
+    #[test]
+    fn test_external_metrics() {
+        let labels_true: DVec<usize> = DVec::from_slice(4, &vec![0, 0, 1, 1]);
+        let labels_pred: DVec<usize> = DVec::from_slice(4, &vec![0, 0, 1, 2]);
+
+        let exp: DMat<usize> = DMat::from_row_vec(2, 3, &vec![2, 0, 0, 0, 1, 1]);
+        assert_eq!(contingency_matrix(&labels_true, &labels_pred), exp);
+
+        assert!((adjusted_rand_score(&labels_true, &labels_pred) - 4. / 7.).abs() < 1e-10);
+
+        // MI = 1/2 log 2 + 2 * 1/4 log 2
+        let mi = 2f64.ln();
+        assert!((mutual_info_score(&labels_true, &labels_pred) - mi).abs() < 1e-10);
+        // H(true) = log 2, H(pred) = 3/2 log 2
+        assert!((normalized_mutual_info_score(&labels_true, &labels_pred) - 0.8).abs() < 1e-10);
+
+        let (h, c, v) = homogeneity_completeness_v_measure(&labels_true, &labels_pred);
+        assert!((h - 1.).abs() < 1e-10);
+        assert!((c - 2. / 3.).abs() < 1e-10);
+        assert!((v - 0.8).abs() < 1e-10);
+
+        // identical labelings up to permutation
+        let labels_pred: DVec<usize> = DVec::from_slice(4, &vec![3, 3, 1, 1]);
+        assert!((adjusted_rand_score(&labels_true, &labels_pred) - 1.).abs() < 1e-10);
+        assert!((normalized_mutual_info_score(&labels_true, &labels_pred) - 1.).abs() < 1e-10);
+        assert!((adjusted_mutual_info_score(&labels_true, &labels_pred) - 1.).abs() < 1e-10);
+
+        // independent labelings are below the expectation of chance
+        let labels_true: DVec<usize> = DVec::from_slice(6, &vec![0, 0, 0, 1, 1, 1]);
+        let labels_pred: DVec<usize> = DVec::from_slice(6, &vec![0, 1, 2, 0, 1, 2]);
+        assert!(mutual_info_score(&labels_true, &labels_pred).abs() < 1e-10);
+        assert!(adjusted_mutual_info_score(&labels_true, &labels_pred) < 0.);
+        assert!(adjusted_rand_score(&labels_true, &labels_pred) < 0.);
+    }
+
+    #[test]
+    fn test_adjusted_mutual_info_single_cluster() {
+        // cluster sizes a = 3 and b = 4 exceed n = 4 in total
+        let labels_true: DVec<usize> = DVec::from_slice(4, &vec![0, 0, 0, 1]);
+        let labels_pred: DVec<usize> = DVec::from_slice(4, &vec![0, 0, 0, 0]);
+        let table = contingency_matrix(&labels_true, &labels_pred);
+        // a single cluster always shares the same information
+        assert!(expected_mutual_info(&table).abs() < 1e-10);
+        assert!(adjusted_mutual_info_score(&labels_true, &labels_pred).abs() < 1e-10);
+    }
+}
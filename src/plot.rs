@@ -0,0 +1,243 @@
+//! Plotting helpers using gnuplot, enabled by "plot" feature
+//!
+//! Plots are written to PNG or SVG files (decided by the extension of the path)
+//! without opening a window. gnuplot must be installed.
+
+extern crate gnuplot;
+extern crate nalgebra;
+
+use gnuplot::{Figure, AxesCommon, Color, Caption, PointSymbol, PointSize, Axis};
+use nalgebra::{DVec, DMat};
+use std::f64;
+
+use super::hclust::{HClust, Cluster};
+use super::kmeans::KMeans;
+use super::pca::PCA;
+
+const COLORS: [&'static str; 8] = ["blue", "red", "green", "orange", "purple",
+                                   "cyan", "brown", "magenta"];
+
+/// draw dendrogram of HClust, leaves are named by labels or record numbers
+pub fn plot_dendrogram(hclust: &HClust, labels: Option<&[String]>, path: &str) {
+    let order = hclust.order();
+    let mut fg = Figure::new();
+    {
+        let ax = fg.axes2d();
+        for (xs, ys) in dendrogram_lines(hclust) {
+            ax.lines(&xs, &ys, &[Color("black")]);
+        }
+        for (x, &record) in order.iter().enumerate() {
+            let name = match labels {
+                Some(l) => l[record].clone(),
+                None => record.to_string()
+            };
+            ax.label(&name, Axis(x as f64), Axis(0.), &[]);
+        }
+        ax.set_y_label("distance", &[]);
+    }
+    write_figure(&mut fg, path);
+}
+
+/// draw records colored by clusters of KMeans and centroids, using the first 2 columns
+pub fn plot_clusters(data: &DMat<f64>, kmeans: &KMeans, path: &str) {
+    assert!(data.ncols() >= 2, "Data must have 2 or more columns");
+    let predicted = kmeans.predict(data);
+    let centroids = kmeans.centroids();
+
+    let mut fg = Figure::new();
+    {
+        let ax = fg.axes2d();
+        for c in 0..kmeans.nclusters {
+            let rows: Vec<usize> = (0..data.nrows()).filter(|&r| predicted[r] == c).collect();
+            let xs: Vec<f64> = rows.iter().map(|&r| data[(r, 0)]).collect();
+            let ys: Vec<f64> = rows.iter().map(|&r| data[(r, 1)]).collect();
+            let caption = format!("cluster {}", c);
+            ax.points(&xs, &ys, &[Color(COLORS[c % COLORS.len()]), Caption(&caption)]);
+
+            ax.points([centroids[(c, 0)]], [centroids[(c, 1)]],
+                      &[Color("black"), PointSymbol('x'), PointSize(2.)]);
+        }
+    }
+    write_figure(&mut fg, path);
+}
+
+/// draw scores of the first 2 principal components and loadings of features as arrows
+pub fn plot_biplot(data: &DMat<f64>, pca: &mut PCA, feature_names: Option<&[String]>,
+                   path: &str) {
+    let scores = pca.transform(data);
+    let xs: Vec<f64> = (0..scores.nrows()).map(|r| scores[(r, 0)]).collect();
+    let ys: Vec<f64> = (0..scores.nrows()).map(|r| scores[(r, 1)]).collect();
+
+    // scale loadings to the range of scores
+    let max_score = xs.iter().chain(ys.iter()).fold(0., |a: f64, v| a.max(v.abs()));
+    let max_loading = (0..pca.rotation.nrows()).fold(0., |a: f64, f| {
+        a.max(pca.rotation[(f, 0)].abs()).max(pca.rotation[(f, 1)].abs())
+    });
+    let scale = if max_loading > 0. { max_score / max_loading } else { 1. };
+
+    let mut fg = Figure::new();
+    {
+        let ax = fg.axes2d();
+        ax.points(&xs, &ys, &[Color("blue")]);
+        for f in 0..pca.rotation.nrows() {
+            let (x, y) = (pca.rotation[(f, 0)] * scale, pca.rotation[(f, 1)] * scale);
+            ax.arrow(Axis(0.), Axis(0.), Axis(x), Axis(y), &[Color("red")]);
+            let name = match feature_names {
+                Some(n) => n[f].clone(),
+                None => format!("x{}", f)
+            };
+            ax.label(&name, Axis(x), Axis(y), &[]);
+        }
+        ax.set_x_label("PC1", &[]);
+        ax.set_y_label("PC2", &[]);
+    }
+    write_figure(&mut fg, path);
+}
+
+/// draw records of 2 columns colored by labels (-1 or 1) and the boundary where the
+/// decision function is 0, e.g. `|x| svc.decision_function(x)`
+pub fn plot_decision_boundary<F>(data: &DMat<f64>, y: &DVec<f64>, decision: F, path: &str)
+    where F: Fn(&DMat<f64>) -> DVec<f64> {
+
+    assert!(data.ncols() == 2, "Data must have 2 columns");
+    let (bx, by) = boundary_points(data, &decision, 100);
+
+    let mut fg = Figure::new();
+    {
+        let ax = fg.axes2d();
+        for (i, &label) in [-1., 1.].iter().enumerate() {
+            let rows: Vec<usize> = (0..data.nrows()).filter(|&r| y[r] == label).collect();
+            let xs: Vec<f64> = rows.iter().map(|&r| data[(r, 0)]).collect();
+            let ys: Vec<f64> = rows.iter().map(|&r| data[(r, 1)]).collect();
+            let caption = format!("{}", label);
+            ax.points(&xs, &ys, &[Color(COLORS[i]), Caption(&caption)]);
+        }
+        ax.points(&bx, &by, &[Color("black"), PointSymbol('.'), Caption("boundary")]);
+    }
+    write_figure(&mut fg, path);
+}
+
+fn write_figure(fg: &mut Figure, path: &str) {
+    fg.set_terminal(output_terminal(path), path);
+    fg.show();
+}
+
+/// gnuplot terminal from the extension of the path
+fn output_terminal(path: &str) -> &'static str {
+    let lower = path.to_lowercase();
+    if lower.ends_with(".png") {
+        return "png";
+    } else if lower.ends_with(".svg") {
+        return "svg";
+    }
+    panic!("Output must be .png or .svg file");
+}
+
+/// line segments of the dendrogram, leaves are placed at 0, 1, ... in the order of leaves
+fn dendrogram_lines(hclust: &HClust) -> Vec<(Vec<f64>, Vec<f64>)> {
+    let order = hclust.order();
+    let mut lines = vec![];
+    dendrogram_position(hclust.root(), &order, &mut lines);
+    return lines;
+}
+
+/// x coordinate of the cluster, adding ㄇ shaped lines connecting children
+fn dendrogram_position(cluster: &Cluster, order: &[usize],
+                       lines: &mut Vec<(Vec<f64>, Vec<f64>)>) -> f64 {
+    if cluster.is_leaf() {
+        return order.iter().position(|&r| r == cluster.id()).unwrap() as f64;
+    }
+    let children = cluster.children();
+    let left = dendrogram_position(&children[0], order, lines);
+    let right = dendrogram_position(&children[1], order, lines);
+    lines.push((vec![left, left, right, right],
+                vec![children[0].distance(), cluster.distance(),
+                     cluster.distance(), children[1].distance()]));
+    return (left + right) / 2.;
+}
+
+/// points where the decision function is 0, interpolated on the grid covering the data
+fn boundary_points<F>(data: &DMat<f64>, decision: &F, resolution: usize) -> (Vec<f64>, Vec<f64>)
+    where F: Fn(&DMat<f64>) -> DVec<f64> {
+
+    let range = |col: usize| {
+        let values: Vec<f64> = (0..data.nrows()).map(|r| data[(r, col)]).collect();
+        let min = values.iter().fold(f64::INFINITY, |a, &v| a.min(v));
+        let max = values.iter().fold(f64::NEG_INFINITY, |a, &v| a.max(v));
+        let margin = (max - min) * 0.05;
+        (min - margin, (max - min + 2. * margin) / resolution as f64)
+    };
+    let (x0, dx) = range(0);
+    let (y0, dy) = range(1);
+
+    // grid of (resolution + 1) x (resolution + 1) points, index is i * (resolution + 1) + j
+    let npoints = resolution + 1;
+    let grid = DMat::from_fn(npoints * npoints, 2, |k, c| {
+        if c == 0 { x0 + (k / npoints) as f64 * dx } else { y0 + (k % npoints) as f64 * dy }
+    });
+    let values = decision(&grid).at;
+
+    let mut xs = vec![];
+    let mut ys = vec![];
+    for i in 0..npoints {
+        for j in 0..npoints {
+            let k = i * npoints + j;
+            // neighbors in x and y directions
+            let mut neighbors = vec![];
+            if i + 1 < npoints {
+                neighbors.push(k + npoints);
+            }
+            if j + 1 < npoints {
+                neighbors.push(k + 1);
+            }
+            for l in neighbors {
+                let (v1, v2) = (values[k], values[l]);
+                if (v1 < 0.) != (v2 < 0.) {
+                    let t = v1 / (v1 - v2);
+                    xs.push(grid[(k, 0)] + t * (grid[(l, 0)] - grid[(k, 0)]));
+                    ys.push(grid[(k, 1)] + t * (grid[(l, 1)] - grid[(k, 1)]));
+                }
+            }
+        }
+    }
+    return (xs, ys);
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{output_terminal, dendrogram_lines, boundary_points};
+    use super::super::hclust::{HClust, ClusterDistance};
+
+    #[test]
+    fn test_output_terminal() {
+        assert_eq!(output_terminal("hclust.png"), "png");
+        assert_eq!(output_terminal("hclust.SVG"), "svg");
+    }
+
+    #[test]
+    fn test_dendrogram_lines() {
+        // (0, 1) are merged at 2, then merged with 2 at 6
+        let mut hclust = HClust::new(ClusterDistance::Single);
+        hclust.fit_dist(&DMat::from_row_vec(1, 3, &vec![2., 6., 6.]));
+
+        // leaves are placed in the order of 1, 0, 2
+        let lines = dendrogram_lines(&hclust);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], (vec![0., 0., 1., 1.], vec![0., 2., 2., 0.]));
+        assert_eq!(lines[1], (vec![0.5, 0.5, 2., 2.], vec![2., 6., 6., 0.]));
+    }
+
+    #[test]
+    fn test_boundary_points() {
+        let data: DMat<f64> = DMat::from_row_vec(2, 2, &vec![0., 0., 10., 10.]);
+        // boundary is x = 5
+        let decision = |x: &DMat<f64>| DVec::from_fn(x.nrows(), |r| x[(r, 0)] - 5.);
+        let (xs, ys) = boundary_points(&data, &decision, 10);
+        assert_eq!(xs.len(), 11);
+        assert_eq!(ys.len(), 11);
+        for x in xs {
+            assert!((x - 5.).abs() < 1e-10);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+extern crate nalgebra;
+
+use nalgebra::{DVec, DMat};
+
+/// Row-wise access to a data matrix, used by solvers which visit one record at a time
+pub trait Rows {
+    fn nrows(&self) -> usize;
+    fn ncols(&self) -> usize;
+
+    /// inner product of a row and a dense vector
+    fn row_dot(&self, i: usize, w: &DVec<f64>) -> f64;
+
+    /// w += a * row
+    fn row_axpy(&self, i: usize, a: f64, w: &mut DVec<f64>);
+
+    /// squared norm of a row
+    fn row_sqnorm(&self, i: usize) -> f64;
+}
+
+impl Rows for DMat<f64> {
+
+    fn nrows(&self) -> usize {
+        return DMat::nrows(self);
+    }
+
+    fn ncols(&self) -> usize {
+        return DMat::ncols(self);
+    }
+
+    fn row_dot(&self, i: usize, w: &DVec<f64>) -> f64 {
+        assert!(self.ncols() == w.len());
+        return (0..w.len()).fold(0., |a, j| a + self[(i, j)] * w[j]);
+    }
+
+    fn row_axpy(&self, i: usize, a: f64, w: &mut DVec<f64>) {
+        assert!(self.ncols() == w.len());
+        for j in 0..w.len() {
+            w[j] += a * self[(i, j)];
+        }
+    }
+
+    fn row_sqnorm(&self, i: usize) -> f64 {
+        return (0..DMat::ncols(self)).fold(0., |a, j| a + self[(i, j)] * self[(i, j)]);
+    }
+}
+
+/// Sparse matrix in compressed sparse row (CSR) format
+pub struct CsrMat {
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,         // row i is stored in indptr[i]..indptr[i + 1]
+    indices: Vec<usize>,        // column numbers of values
+    values: Vec<f64>
+}
+
+impl CsrMat {
+
+    pub fn new(nrows: usize, ncols: usize, indptr: Vec<usize>,
+               indices: Vec<usize>, values: Vec<f64>) -> CsrMat {
+        assert!(indptr.len() == nrows + 1);
+        assert!(indices.len() == values.len());
+        assert!(indptr[nrows] == values.len());
+        assert!(indices.iter().all(|&j| j < ncols));
+        CsrMat {
+            nrows: nrows,
+            ncols: ncols,
+            indptr: indptr,
+            indices: indices,
+            values: values
+        }
+    }
+
+    /// create from DMat, dropping zeros
+    pub fn from_dmat(data: &DMat<f64>) -> CsrMat {
+        let mut indptr: Vec<usize> = vec![0];
+        let mut indices: Vec<usize> = vec![];
+        let mut values: Vec<f64> = vec![];
+        for i in 0..data.nrows() {
+            for j in 0..data.ncols() {
+                if data[(i, j)] != 0. {
+                    indices.push(j);
+                    values.push(data[(i, j)]);
+                }
+            }
+            indptr.push(values.len());
+        }
+        return CsrMat::new(data.nrows(), data.ncols(), indptr, indices, values);
+    }
+
+    pub fn to_dmat(&self) -> DMat<f64> {
+        let mut data = DMat::from_elem(self.nrows, self.ncols, 0.);
+        for i in 0..self.nrows {
+            for (j, v) in self.row(i) {
+                data[(i, j)] = v;
+            }
+        }
+        return data;
+    }
+
+    /// number of non-zero elements
+    pub fn nnz(&self) -> usize {
+        return self.values.len();
+    }
+
+    /// (column, value) pairs of non-zero elements in a row
+    pub fn row(&self, i: usize) -> Vec<(usize, f64)> {
+        let (start, end) = (self.indptr[i], self.indptr[i + 1]);
+        return self.indices[start..end].iter().cloned()
+                   .zip(self.values[start..end].iter().cloned())
+                   .collect();
+    }
+}
+
+impl Rows for CsrMat {
+
+    fn nrows(&self) -> usize {
+        return self.nrows;
+    }
+
+    fn ncols(&self) -> usize {
+        return self.ncols;
+    }
+
+    fn row_dot(&self, i: usize, w: &DVec<f64>) -> f64 {
+        assert!(self.ncols == w.len());
+        let mut val = 0.;
+        for k in self.indptr[i]..self.indptr[i + 1] {
+            val += self.values[k] * w[self.indices[k]];
+        }
+        return val;
+    }
+
+    fn row_axpy(&self, i: usize, a: f64, w: &mut DVec<f64>) {
+        assert!(self.ncols == w.len());
+        for k in self.indptr[i]..self.indptr[i + 1] {
+            w[self.indices[k]] += a * self.values[k];
+        }
+    }
+
+    fn row_sqnorm(&self, i: usize) -> f64 {
+        let mut val = 0.;
+        for k in self.indptr[i]..self.indptr[i + 1] {
+            val += self.values[k] * self.values[k];
+        }
+        return val;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{CsrMat, Rows};
+
+    #[test]
+    fn test_csr() {
+        let m: DMat<f64> = DMat::from_row_vec(3, 3, &vec![1., 0., 2.,
+                                                          0., 0., 0.,
+                                                          0., 3., 0.]);
+        let s = CsrMat::from_dmat(&m);
+        assert_eq!(s.nnz(), 3);
+        assert_eq!(s.row(0), vec![(0, 1.), (2, 2.)]);
+        assert_eq!(s.row(1), vec![]);
+        assert_eq!(s.to_dmat(), m);
+
+        let w: DVec<f64> = DVec::from_slice(3, &vec![1., 2., 3.]);
+        for i in 0..3 {
+            assert_eq!(s.row_dot(i, &w), m.row_dot(i, &w));
+            assert_eq!(s.row_sqnorm(i), m.row_sqnorm(i));
+        }
+
+        let mut w1 = DVec::from_elem(3, 0.);
+        s.row_axpy(0, 2., &mut w1);
+        let mut w2 = DVec::from_elem(3, 0.);
+        m.row_axpy(0, 2., &mut w2);
+        assert_eq!(w1, w2);
+    }
+}
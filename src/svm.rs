@@ -3,19 +3,71 @@ extern crate num;
 extern crate rand;
 
 use nalgebra::{DVec, DMat, RowSlice, Iterable};
-use rand::sample;
+use rand::{sample, Rng};
+use std::cmp;
 use std::collections::HashMap;
 use std::f64;
 use std::ops::Index;
 use std::process::exit;
 
-use mathfunc::{inner_product, dvec_copy, dmat_copy};
+use mathfunc::{inner_product, euc_dist, dvec_copy, dmat_copy, dvec_take, dmat_take_rows, get_rng};
+
+/// Weights of labels multiplied to C
+#[derive(Clone)]
+pub enum ClassWeight {
+    Uniform,                            // same C for all labels
+    Balanced,                           // inversely proportional to label frequencies
+    Manual { negative: f64, positive: f64 },    // weights for label -1 and 1
+}
+
+/// Kernel function between 2 records
+#[derive(Clone)]
+pub enum Kernel {
+    Linear,                             // inner product
+    RBF(f64),                           // exp(-gamma |x1 - x2|^2), gamma must be positive
+}
+
+fn kernel_function(kernel: &Kernel, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+    match *kernel {
+        Kernel::Linear => return inner_product(x1, x2),
+        Kernel::RBF(gamma) => {
+            let d = euc_dist(x1, x2);
+            return (- gamma * d * d).exp();
+        }
+    }
+}
+
+/// per-sample upper bounds of alpha, C * class weight * sample weight
+fn get_sample_c(c: f64, class_weight: &ClassWeight, y: &DVec<f64>,
+                sample_weight: &DVec<f64>) -> DVec<f64> {
+    assert!(y.len() == sample_weight.len());
+    let npos = y.iter().filter(|&v| *v == 1.).count() as f64;
+    let nneg = (y.len() as f64) - npos;
+    let n = y.len() as f64;
+
+    let (wneg, wpos) = match *class_weight {
+        ClassWeight::Uniform => (1., 1.),
+        ClassWeight::Balanced => {
+            assert!(npos > 0. && nneg > 0., "Balanced class weight requires both labels -1 and 1");
+            (n / (2. * nneg), n / (2. * npos))
+        },
+        ClassWeight::Manual { negative, positive } => (negative, positive)
+    };
+    return DVec::from_fn(y.len(), |i| {
+        let w = if y[i] == 1. { wpos } else { wneg };
+        c * w * sample_weight[i]
+    });
+}
 
 pub struct SVC {
     c: f64,
     tolerance: f64,
     max_iter: usize,
     kernels: DMat<f64>,
+    cs: DVec<f64>,                  // per-sample C
+
+    pub class_weight: ClassWeight,
+    pub seed: Option<usize>,
 
     data: DMat<f64>,
     y: DVec<f64>,
@@ -32,6 +84,11 @@ pub trait SVMTrait {
     fn new(c: f64, tolerance: f64, max_iter: usize) -> Self;
     fn kernel(&self, i: usize, j: usize) -> f64;
 
+    /// kernel function between 2 records
+    fn kernel_value(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        return inner_product(x1, x2);
+    }
+
     fn get_kernel_matrix(&mut self, data: &DMat<f64>) -> DMat<f64> {
         // build kernel matrix
         let mut values: Vec<f64> = vec![];
@@ -40,7 +97,7 @@ pub trait SVMTrait {
                 if i >= j {
                     let xi = data.row_slice(i, 0, data.ncols());
                     let xj = data.row_slice(j, 0, data.ncols());
-                    values.push(inner_product(&xi, &xj));
+                    values.push(self.kernel_value(&xi, &xj));
                 } else {
                     values.push(0.0);
                 }
@@ -79,6 +136,10 @@ impl SVMTrait for SVC {
             b: 0.0,
 
             beta: DVec::from_elem(1, 0.0),
+
+            cs: DVec::from_elem(1, 0.0),
+            class_weight: ClassWeight::Uniform,
+            seed: None,
         }
     }
 
@@ -94,8 +155,16 @@ impl SVMTrait for SVC {
 impl SVC {
 
     pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<f64>) {
+        let sample_weight = DVec::from_elem(y.len(), 1.);
+        self.fit_with_weights(data, y, &sample_weight);
+    }
+
+    /// fit with per-sample weights which scale C
+    pub fn fit_with_weights(&mut self, data: &DMat<f64>, y: &DVec<f64>,
+                            sample_weight: &DVec<f64>) {
         // build kernel matrix
         self.kernels = self.get_kernel_matrix(&data);
+        self.cs = get_sample_c(self.c, &self.class_weight, y, sample_weight);
 
         // copy data
         self.data = dmat_copy(data);
@@ -108,7 +177,7 @@ impl SVC {
         let mut u = DVec::from_slice(data.nrows(), &y.at);
 
         // 2 レコードをランダムサンプリング
-        let mut rng = rand::thread_rng();
+        let mut rng = get_rng(self.seed);
         let sample: Vec<usize> = sample(&mut rng, 0..data.nrows(), 2);
         let mut s = sample[0];
         let mut t = sample[1];
@@ -132,9 +201,9 @@ impl SVC {
                 let alpha = self.beta[i] / y[i];
 
                 // I_UP
-                if (0. < alpha && alpha < self.c) ||         // I0
+                if (0. < alpha && alpha < self.cs[i]) ||     // I0
                    (alpha == 0. && y[i] == 1.) ||            // I1
-                   (alpha == self.c && y[i] == -1.) {        // I4
+                   (alpha == self.cs[i] && y[i] == -1.) {    // I4
                     if u[i] >= nexts_u && i != s {
                         nexts_u = u[i];
                         nexts = i;
@@ -142,9 +211,9 @@ impl SVC {
                 }
 
                 // I_LOW
-                if (0. < alpha && alpha < self.c) ||         // I0
+                if (0. < alpha && alpha < self.cs[i]) ||     // I0
                    (alpha == 0. && y[i] == -1.) ||           // I2
-                   (alpha == self.c && y[i] == 1.) {         // I3
+                   (alpha == self.cs[i] && y[i] == 1.) {     // I3
                     if u[i] <= nextt_u && i != t {
                         nextt_u = u[i];
                         nextt = i;
@@ -157,7 +226,7 @@ impl SVC {
 
         self.alpha = DVec::from_fn(self.beta.len(), |i| self.beta[i] / y[i]);
         for i in 0..data.nrows() {
-            if 0. < self.alpha[i] && self.alpha[i] < self.c {
+            if 0. < self.alpha[i] && self.alpha[i] < self.cs[i] {
                 self.b = y[i];
                 for j in 0..data.nrows() {
                     self.b -= self.alpha[j] * y[j] * self.kernel(i, j);
@@ -176,6 +245,9 @@ impl SVC {
         let betas = self.beta[s];
         let betat = self.beta[t];
 
+        let cs = self.cs[s];
+        let ct = self.cs[t];
+
         // アルゴリズム 7.1: ステップ 5
         // 式 7.8
         let k = self.kernel(s, s) + self.kernel(t, t) + 2. * self.kernel(s, t);
@@ -190,17 +262,17 @@ impl SVC {
         let mut h = 0.;
         if ys == 1. && yt == 1. {
             // f64 は Ord トレイトを持たないため min / max 関数は利用できない
-            l = (- betas).max(betat - self.c);
-            h = (self.c - betas).min(betat);
+            l = (- betas).max(betat - ct);
+            h = (cs - betas).min(betat);
         } else if ys == 1. && yt == -1. {
             l = (- betas).max(betat);
-            h = (self.c - betas).min(self.c + betat);
+            h = (cs - betas).min(ct + betat);
         } else if ys == -1. && yt == 1. {
-            l = (- self.c - betas).max(betat - self.c);
+            l = (- cs - betas).max(betat - ct);
             h = (- betas).min(betat);
         } else if ys == -1. && yt == -1. {
-            l = (- self.c - betas).max(betat);
-            h = (- betas).min(self.c + betat);
+            l = (- cs - betas).max(betat);
+            h = (- betas).min(ct + betat);
         }
 
         if delta < l {
@@ -223,8 +295,19 @@ pub struct SVC2 {
     tolerance: f64,
     max_iter: usize,
     kernels: DMat<f64>,
+
+    data: DMat<f64>,
+    y: DVec<f64>,
+    cs: DVec<f64>,                  // per-sample C
+
+    pub class_weight: ClassWeight,
+    pub seed: Option<usize>,        // seed to split folds for probability
+
     pub alpha: DVec<f64>,
     pub b: f64,
+
+    pub probability: bool,      // whether to fit sigmoid for predict_proba
+    pub prob: Option<(f64, f64)>,   // Platt scaling parameters A and B, fitted when probability
 }
 
 impl SVMTrait for SVC2 {
@@ -236,8 +319,18 @@ impl SVMTrait for SVC2 {
             max_iter: max_iter,
             kernels: DMat::from_elem(1, 1, 0.0),
 
+            data: DMat::from_elem(1, 1, 0.0),
+            y: DVec::from_elem(1, 0.0),
+            cs: DVec::from_elem(1, 0.0),
+
+            class_weight: ClassWeight::Uniform,
+            seed: None,
+
             alpha: DVec::from_elem(1, 0.0),
             b: 0.0,
+
+            probability: false,
+            prob: None,
         }
     }
 
@@ -253,8 +346,21 @@ impl SVMTrait for SVC2 {
 impl SVC2 {
 
     pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<f64>) {
+        let sample_weight = DVec::from_elem(y.len(), 1.);
+        self.fit_with_weights(data, y, &sample_weight);
+    }
+
+    /// fit with per-sample weights which scale C
+    pub fn fit_with_weights(&mut self, data: &DMat<f64>, y: &DVec<f64>,
+                            sample_weight: &DVec<f64>) {
         // build kernel matrix
         self.kernels = self.get_kernel_matrix(&data);
+        self.cs = get_sample_c(self.C, &self.class_weight, y, sample_weight);
+
+        // copy data
+        self.data = dmat_copy(data);
+        self.y = dvec_copy(y);
+
         // init param
         self.alpha = DVec::from_elem(data.nrows(), 0.0);
 
@@ -271,7 +377,7 @@ impl SVC2 {
                 let ei = errors[i];
 
                 // KKT conditions
-                if (yi * ei < -self.tolerance && ai < self.C) ||
+                if (yi * ei < -self.tolerance && ai < self.cs[i]) ||
                    (yi * ei > self.tolerance && ai > 0.) {
                     for j in 0..y.len() {
                         let updated_step = self.take_step(&y, &errors, i, j);
@@ -286,6 +392,85 @@ impl SVC2 {
                 break;
             }
         }
+
+        self.prob = if self.probability {
+            let dec_values = self.cross_validation_decision(data, y, 5);
+            Some(sigmoid_train(&dec_values, y))
+        } else {
+            None
+        };
+    }
+
+    /// decision values of records, positive for label 1
+    pub fn decision_function(&self, data: &DMat<f64>) -> DVec<f64> {
+        return DVec::from_fn(data.nrows(), |x| {
+            let row = data.row_slice(x, 0, data.ncols());
+            let mut v = self.b;
+            for i in 0..self.alpha.len() {
+                if self.alpha[i] != 0. {
+                    let xi = self.data.row_slice(i, 0, self.data.ncols());
+                    v += self.alpha[i] * self.y[i] * self.kernel_value(&xi, &row);
+                }
+            }
+            v
+        });
+    }
+
+    /// predicted labels (-1 or 1)
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<f64> {
+        let dec_values = self.decision_function(data);
+        return DVec::from_fn(dec_values.len(), |i| if dec_values[i] > 0. { 1. } else { -1. });
+    }
+
+    /// probabilities of labels, columns correspond to -1 and 1
+    pub fn predict_proba(&self, data: &DMat<f64>) -> DMat<f64> {
+        let (a, b) = match self.prob {
+            Some(prob) => prob,
+            None => panic!("SVC2 must be fitted with probability = true")
+        };
+        let dec_values = self.decision_function(data);
+        return DMat::from_fn(data.nrows(), 2, |i, j| {
+            let p = sigmoid_predict(dec_values[i], a, b);
+            if j == 1 { p } else { 1. - p }
+        });
+    }
+
+    /// decision values of each record predicted by the model trained on other folds
+    fn cross_validation_decision(&self, data: &DMat<f64>, y: &DVec<f64>,
+                                 nfolds: usize) -> DVec<f64> {
+        let n = data.nrows();
+        let mut rng = get_rng(self.seed);
+        let mut perm: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut perm);
+
+        let mut dec_values = DVec::from_elem(n, 0.);
+        for k in 0..nfolds {
+            let start = k * n / nfolds;
+            let end = (k + 1) * n / nfolds;
+            let test: Vec<usize> = perm[start..end].to_vec();
+            let mut train: Vec<usize> = perm[0..start].to_vec();
+            train.extend(perm[end..n].iter().cloned());
+
+            let train_y = dvec_take(y, &train);
+            let npos = train_y.iter().filter(|&v| *v == 1.).count();
+
+            if npos == 0 || npos == train.len() {
+                // only one label in training folds
+                for &i in &test {
+                    dec_values[i] = if npos == 0 { -1. } else { 1. };
+                }
+            } else {
+                // keep per-sample C of the whole data
+                let weights = DVec::from_fn(train.len(), |i| self.cs[train[i]] / self.C);
+                let mut model = SVC2::new(self.C, self.tolerance, self.max_iter);
+                model.fit_with_weights(&dmat_take_rows(data, &train), &train_y, &weights);
+                let values = model.decision_function(&dmat_take_rows(data, &test));
+                for (v, &i) in values.iter().zip(test.iter()) {
+                    dec_values[i] = *v;
+                }
+            }
+        }
+        return dec_values;
     }
 
     fn take_step(&mut self, y: &DVec<f64>, errors: &DVec<f64>,
@@ -298,6 +483,9 @@ impl SVC2 {
         let ai = self.alpha[i];
         let ei = errors[i];
 
+        let ci = self.cs[i];
+        let cj = self.cs[j];
+
         let kii = self.kernel(i, i);
         let kjj = self.kernel(j, j);
         let kij = self.kernel(i, j);
@@ -321,11 +509,11 @@ impl SVC2 {
         if yi == yj {
             // same label
             // f64 は Ord トレイトを持たないため min / max 関数は利用できない
-            l = (aj + ai - self.C).max(0.);
-            h = self.C.min(aj + ai);
+            l = (aj + ai - ci).max(0.);
+            h = cj.min(aj + ai);
         } else {
             l = (aj - ai).max(0.);
-            h = self.C.min(aj - ai + self.C);
+            h = cj.min(aj - ai + ci);
         }
         if h == l {
             return false;
@@ -351,9 +539,9 @@ impl SVC2 {
         let b1_new = self.b - ei - yi * kii * (ai_new - ai) - yj * kij * (aj_new - aj);
         let b2_new = self.b - ej - yi * kij * (ai_new - ai) - yj * kjj * (aj_new - aj);
 
-        if ai_new > 0. && ai_new < self.C {
+        if ai_new > 0. && ai_new < ci {
             self.b = b1_new;
-        } else if aj_new > 0. && aj_new < self.C {
+        } else if aj_new > 0. && aj_new < cj {
             self.b = b2_new;
         } else {
             self.b = (b1_new + b2_new) / 2.;
@@ -362,3 +550,1004 @@ impl SVC2 {
     }
 }
 
+/// Fit sigmoid 1 / (1 + exp(A f + B)) to decision values (Platt scaling)
+///
+/// Uses Newton method with backtracking proposed by Lin, Lin and Weng.
+fn sigmoid_train(dec_values: &DVec<f64>, y: &DVec<f64>) -> (f64, f64) {
+    let prior1 = y.iter().filter(|&v| *v == 1.).count() as f64;
+    let prior0 = (y.len() as f64) - prior1;
+
+    let max_iter = 100;
+    let min_step = 1e-10;
+    let sigma = 1e-12;
+    let eps = 1e-5;
+
+    // regularized target probabilities
+    let hi_target = (prior1 + 1.) / (prior1 + 2.);
+    let lo_target = 1. / (prior0 + 2.);
+    let t = DVec::from_fn(y.len(), |i| if y[i] == 1. { hi_target } else { lo_target });
+
+    let objective = |a: f64, b: f64| -> f64 {
+        let mut fval = 0.;
+        for i in 0..dec_values.len() {
+            let f_ab = dec_values[i] * a + b;
+            if f_ab >= 0. {
+                fval += t[i] * f_ab + (1. + (- f_ab).exp()).ln();
+            } else {
+                fval += (t[i] - 1.) * f_ab + (1. + f_ab.exp()).ln();
+            }
+        }
+        fval
+    };
+
+    let mut a = 0.;
+    let mut b = ((prior0 + 1.) / (prior1 + 1.)).ln();
+    let mut fval = objective(a, b);
+
+    for _ in 0..max_iter {
+        // gradient and hessian
+        let mut h11 = sigma;
+        let mut h22 = sigma;
+        let mut h21 = 0.;
+        let mut g1 = 0.;
+        let mut g2 = 0.;
+        for i in 0..dec_values.len() {
+            let f_ab = dec_values[i] * a + b;
+            let (p, q) = if f_ab >= 0. {
+                ((- f_ab).exp() / (1. + (- f_ab).exp()), 1. / (1. + (- f_ab).exp()))
+            } else {
+                (1. / (1. + f_ab.exp()), f_ab.exp() / (1. + f_ab.exp()))
+            };
+            let d2 = p * q;
+            h11 += dec_values[i] * dec_values[i] * d2;
+            h22 += d2;
+            h21 += dec_values[i] * d2;
+            let d1 = t[i] - p;
+            g1 += dec_values[i] * d1;
+            g2 += d1;
+        }
+
+        if g1.abs() < eps && g2.abs() < eps {
+            break;
+        }
+
+        // Newton direction
+        let det = h11 * h22 - h21 * h21;
+        let da = - (h22 * g1 - h21 * g2) / det;
+        let db = - (- h21 * g1 + h11 * g2) / det;
+        let gd = g1 * da + g2 * db;
+
+        // line search
+        let mut step = 1.;
+        while step >= min_step {
+            let new_a = a + step * da;
+            let new_b = b + step * db;
+            let new_f = objective(new_a, new_b);
+            if new_f < fval + 0.0001 * step * gd {
+                a = new_a;
+                b = new_b;
+                fval = new_f;
+                break;
+            }
+            step = step / 2.;
+        }
+        if step < min_step {
+            break;
+        }
+    }
+    return (a, b);
+}
+
+/// Probability of label 1 from decision value
+fn sigmoid_predict(dec_value: f64, a: f64, b: f64) -> f64 {
+    let f_ab = dec_value * a + b;
+    // avoid overflow of exp
+    if f_ab >= 0. {
+        return (- f_ab).exp() / (1. + (- f_ab).exp());
+    } else {
+        return 1. / (1. + f_ab.exp());
+    }
+}
+
+/// Multiclass SVC combining one-vs-one SVC2
+pub struct MultiSVC {
+    c: f64,
+    tolerance: f64,
+    max_iter: usize,
+
+    pub probability: bool,
+    pub seed: Option<usize>,
+    pub classes: Vec<f64>,
+    models: Vec<SVC2>,          // classifiers of (i, j) pairs, i < j
+}
+
+impl MultiSVC {
+
+    pub fn new(c: f64, tolerance: f64, max_iter: usize) -> MultiSVC {
+        MultiSVC {
+            c: c,
+            tolerance: tolerance,
+            max_iter: max_iter,
+
+            probability: false,
+            seed: None,
+            classes: vec![],
+            models: vec![],
+        }
+    }
+
+    pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<f64>) {
+        // sorted unique labels
+        let mut classes: Vec<f64> = vec![];
+        for &v in y.iter() {
+            if !classes.contains(&v) {
+                classes.push(v);
+            }
+        }
+        classes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        self.classes = classes;
+
+        self.models.clear();
+        let k = self.classes.len();
+        for i in 0..k {
+            for j in (i + 1)..k {
+                let indices: Vec<usize> = (0..y.len())
+                    .filter(|&r| y[r] == self.classes[i] || y[r] == self.classes[j])
+                    .collect();
+                // class i as 1, class j as -1
+                let sub_y = DVec::from_fn(indices.len(),
+                                          |r| if y[indices[r]] == self.classes[i] { 1. } else { -1. });
+                let mut model = SVC2::new(self.c, self.tolerance, self.max_iter);
+                model.probability = self.probability;
+                model.seed = self.seed;
+                model.fit(&dmat_take_rows(data, &indices), &sub_y);
+                self.models.push(model);
+            }
+        }
+    }
+
+    /// predicted labels by voting
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<f64> {
+        let k = self.classes.len();
+        let dec_values: Vec<DVec<f64>> = self.models.iter()
+                                                    .map(|m| m.decision_function(data))
+                                                    .collect();
+        return DVec::from_fn(data.nrows(), |r| {
+            let mut votes = vec![0; k];
+            let mut p = 0;
+            for i in 0..k {
+                for j in (i + 1)..k {
+                    if dec_values[p][r] > 0. {
+                        votes[i] += 1;
+                    } else {
+                        votes[j] += 1;
+                    }
+                    p += 1;
+                }
+            }
+            let mut best = 0;
+            for i in 0..k {
+                if votes[i] > votes[best] {
+                    best = i;
+                }
+            }
+            self.classes[best]
+        });
+    }
+
+    /// probabilities of each class (columns ordered as classes) by pairwise coupling
+    pub fn predict_proba(&self, data: &DMat<f64>) -> DMat<f64> {
+        if self.models.iter().any(|m| m.prob.is_none()) {
+            panic!("MultiSVC must be fitted with probability = true");
+        }
+        let k = self.classes.len();
+        let min_prob = 1e-7;
+        let dec_values: Vec<DVec<f64>> = self.models.iter()
+                                                    .map(|m| m.decision_function(data))
+                                                    .collect();
+
+        let mut values: Vec<f64> = vec![];
+        for row in 0..data.nrows() {
+            // pairwise probabilities r_ij = P(i | i or j)
+            let mut r = DMat::from_elem(k, k, 0.);
+            let mut p = 0;
+            for i in 0..k {
+                for j in (i + 1)..k {
+                    let (a, b) = self.models[p].prob.unwrap();
+                    let prob = sigmoid_predict(dec_values[p][row], a, b);
+                    let prob = prob.max(min_prob).min(1. - min_prob);
+                    r[(i, j)] = prob;
+                    r[(j, i)] = 1. - prob;
+                    p += 1;
+                }
+            }
+            values.extend(multiclass_probability(&r).iter().cloned());
+        }
+        return DMat::from_row_vec(data.nrows(), k, &values);
+    }
+}
+
+/// Pairwise coupling proposed by Wu, Lin and Weng (method 2)
+fn multiclass_probability(r: &DMat<f64>) -> DVec<f64> {
+    let k = r.nrows();
+    let kf = k as f64;
+    let max_iter = cmp::max(100, k);
+    let eps = 0.005 / kf;
+
+    let mut q = DMat::from_elem(k, k, 0.);
+    let mut p = DVec::from_elem(k, 1. / kf);
+    for t in 0..k {
+        for j in 0..k {
+            if j < t {
+                q[(t, t)] += r[(j, t)] * r[(j, t)];
+                q[(t, j)] = q[(j, t)];
+            } else if j > t {
+                q[(t, t)] += r[(j, t)] * r[(j, t)];
+                q[(t, j)] = - r[(j, t)] * r[(t, j)];
+            }
+        }
+    }
+
+    let mut qp = DVec::from_elem(k, 0.);
+    for _ in 0..max_iter {
+        let mut pqp = 0.;
+        for t in 0..k {
+            qp[t] = 0.;
+            for j in 0..k {
+                qp[t] += q[(t, j)] * p[j];
+            }
+            pqp += p[t] * qp[t];
+        }
+        let max_error = (0..k).fold(0., |e: f64, t| e.max((qp[t] - pqp).abs()));
+        if max_error < eps {
+            break;
+        }
+
+        for t in 0..k {
+            let diff = (- qp[t] + pqp) / q[(t, t)];
+            p[t] += diff;
+            pqp = (pqp + diff * (diff * q[(t, t)] + 2. * qp[t])) / (1. + diff) / (1. + diff);
+            for j in 0..k {
+                qp[j] = (qp[j] + diff * q[(t, j)]) / (1. + diff);
+                p[j] /= 1. + diff;
+            }
+        }
+    }
+    return p;
+}
+
+/// Solve the generalized SMO problem (same as LIBSVM)
+///
+///   min 1/2 a' Q a + p' a  s.t.  y' a = const, 0 <= a_i <= c_i
+///
+/// `q(i, j)` must return y_i y_j K_ij, so that callers can map
+/// duplicated variables (e.g. SVR) onto the same kernel matrix.
+/// When `nu` is true, working sets are chosen within the same sign of y
+/// to keep the additional equality constraint of nu-SVM.
+///
+/// Returns (alpha, rho, r), decision function is sum(y_i a_i K(x_i, x)) - rho
+fn solve_smo<F>(q: &F, p: &DVec<f64>, y: &DVec<f64>, c: &DVec<f64>,
+                alpha: DVec<f64>, tolerance: f64, max_iter: usize,
+                nu: bool) -> (DVec<f64>, f64, f64)
+    where F: Fn(usize, usize) -> f64 {
+
+    let l = p.len();
+    let mut alpha = alpha;
+
+    // gradient G = Q a + p
+    let mut grad = dvec_copy(p);
+    for i in 0..l {
+        if alpha[i] != 0. {
+            for k in 0..l {
+                grad[k] += alpha[i] * q(i, k);
+            }
+        }
+    }
+
+    for _ in 0..max_iter {
+        let (i, j) = match select_working_set(y, c, &alpha, &grad, tolerance, nu) {
+            Some(pair) => pair,
+            None => break
+        };
+
+        let ai = alpha[i];
+        let aj = alpha[j];
+        let qij = q(i, j);
+
+        if y[i] != y[j] {
+            let mut quad = q(i, i) + q(j, j) + 2. * qij;
+            if quad <= 0. {
+                quad = 1e-12;
+            }
+            let delta = (- grad[i] - grad[j]) / quad;
+            let diff = ai - aj;
+            alpha[i] = ai + delta;
+            alpha[j] = aj + delta;
+
+            // clip to the box
+            if diff > 0. {
+                if alpha[j] < 0. {
+                    alpha[j] = 0.;
+                    alpha[i] = diff;
+                }
+            } else {
+                if alpha[i] < 0. {
+                    alpha[i] = 0.;
+                    alpha[j] = - diff;
+                }
+            }
+            if diff > c[i] - c[j] {
+                if alpha[i] > c[i] {
+                    alpha[i] = c[i];
+                    alpha[j] = c[i] - diff;
+                }
+            } else {
+                if alpha[j] > c[j] {
+                    alpha[j] = c[j];
+                    alpha[i] = c[j] + diff;
+                }
+            }
+        } else {
+            let mut quad = q(i, i) + q(j, j) - 2. * qij;
+            if quad <= 0. {
+                quad = 1e-12;
+            }
+            let delta = (grad[i] - grad[j]) / quad;
+            let sum = ai + aj;
+            alpha[i] = ai - delta;
+            alpha[j] = aj + delta;
+
+            // clip to the box
+            if sum > c[i] {
+                if alpha[i] > c[i] {
+                    alpha[i] = c[i];
+                    alpha[j] = sum - c[i];
+                }
+            } else {
+                if alpha[j] < 0. {
+                    alpha[j] = 0.;
+                    alpha[i] = sum;
+                }
+            }
+            if sum > c[j] {
+                if alpha[j] > c[j] {
+                    alpha[j] = c[j];
+                    alpha[i] = sum - c[j];
+                }
+            } else {
+                if alpha[i] < 0. {
+                    alpha[i] = 0.;
+                    alpha[j] = sum;
+                }
+            }
+        }
+
+        // update gradient
+        let dai = alpha[i] - ai;
+        let daj = alpha[j] - aj;
+        for k in 0..l {
+            grad[k] += q(i, k) * dai + q(j, k) * daj;
+        }
+    }
+
+    if nu {
+        // calculate_rho uses y G, so r2 has the opposite sign to LIBSVM
+        let r1 = calculate_rho(y, c, &alpha, &grad, Some(1.));
+        let r2 = calculate_rho(y, c, &alpha, &grad, Some(-1.));
+        return (alpha, (r1 + r2) / 2., (r1 - r2) / 2.);
+    } else {
+        let rho = calculate_rho(y, c, &alpha, &grad, None);
+        return (alpha, rho, 0.);
+    }
+}
+
+/// select maximal violating pair, returns None when KKT conditions are satisfied
+fn select_working_set(y: &DVec<f64>, c: &DVec<f64>, alpha: &DVec<f64>,
+                      grad: &DVec<f64>, tolerance: f64,
+                      nu: bool) -> Option<(usize, usize)> {
+    // candidates of working set, for each sign of y when nu is true
+    let signs: Vec<Option<f64>> = match nu {
+        true => vec![Some(1.), Some(-1.)],
+        false => vec![None]
+    };
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut best_gap = tolerance;
+
+    for sign in signs {
+        let mut gmax = f64::NEG_INFINITY;
+        let mut gmin = f64::INFINITY;
+        let mut tmp_i = 0;
+        let mut tmp_j = 0;
+
+        for t in 0..y.len() {
+            match sign {
+                Some(s) if y[t] != s => continue,
+                _ => {}
+            }
+            let v = - y[t] * grad[t];
+            // I_UP
+            if (y[t] == 1. && alpha[t] < c[t]) || (y[t] == -1. && alpha[t] > 0.) {
+                if v >= gmax {
+                    gmax = v;
+                    tmp_i = t;
+                }
+            }
+            // I_LOW
+            if (y[t] == 1. && alpha[t] > 0.) || (y[t] == -1. && alpha[t] < c[t]) {
+                if v <= gmin {
+                    gmin = v;
+                    tmp_j = t;
+                }
+            }
+        }
+        if gmax - gmin > best_gap {
+            best_gap = gmax - gmin;
+            best = Some((tmp_i, tmp_j));
+        }
+    }
+    return best;
+}
+
+/// calculate rho (= -b) from the gradient, optionally only using the specified sign of y
+fn calculate_rho(y: &DVec<f64>, c: &DVec<f64>, alpha: &DVec<f64>,
+                 grad: &DVec<f64>, sign: Option<f64>) -> f64 {
+    let mut ub = f64::INFINITY;
+    let mut lb = f64::NEG_INFINITY;
+    let mut nfree = 0;
+    let mut sum_free = 0.;
+
+    for i in 0..y.len() {
+        match sign {
+            Some(s) if y[i] != s => continue,
+            _ => {}
+        }
+        let yg = y[i] * grad[i];
+        if alpha[i] >= c[i] {
+            if y[i] == -1. {
+                ub = ub.min(yg);
+            } else {
+                lb = lb.max(yg);
+            }
+        } else if alpha[i] <= 0. {
+            if y[i] == 1. {
+                ub = ub.min(yg);
+            } else {
+                lb = lb.max(yg);
+            }
+        } else {
+            nfree += 1;
+            sum_free += yg;
+        }
+    }
+    if nfree > 0 {
+        return sum_free / (nfree as f64);
+    } else {
+        return (ub + lb) / 2.;
+    }
+}
+
+/// Loss function of SVR
+pub enum SVRLoss {
+    Epsilon(f64),           // epsilon-SVR, width of the insensitive tube
+    Nu(f64),                // nu-SVR, upper bound of the fraction of errors
+}
+
+pub struct SVR {
+    c: f64,
+    tolerance: f64,
+    max_iter: usize,
+    kernels: DMat<f64>,
+
+    pub kernel_type: Kernel,
+
+    data: DMat<f64>,
+
+    pub loss: SVRLoss,
+    pub coef: DVec<f64>,            // alpha - alpha*
+    pub b: f64,
+    pub epsilon: f64,               // tube width (estimated in nu-SVR)
+}
+
+impl SVMTrait for SVR {
+
+    fn new(c: f64, tolerance: f64, max_iter: usize) -> SVR {
+        SVR {
+            c: c,
+            tolerance: tolerance,
+            max_iter: max_iter,
+            kernels: DMat::from_elem(1, 1, 0.0),
+
+            kernel_type: Kernel::Linear,
+
+            data: DMat::from_elem(1, 1, 0.0),
+
+            loss: SVRLoss::Epsilon(0.1),
+            coef: DVec::from_elem(1, 0.0),
+            b: 0.0,
+            epsilon: 0.1,
+        }
+    }
+
+    fn kernel(&self, i: usize, j: usize) -> f64 {
+        if i >= j {
+            return *self.kernels.index((i, j));
+        } else {
+            return *self.kernels.index((j, i));
+        }
+    }
+
+    fn kernel_value(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        return kernel_function(&self.kernel_type, x1, x2);
+    }
+}
+
+impl SVR {
+
+    pub fn fit(&mut self, data: &DMat<f64>, y: &DVec<f64>) {
+        // build kernel matrix
+        self.kernels = self.get_kernel_matrix(&data);
+        self.data = dmat_copy(data);
+
+        let n = data.nrows();
+
+        // variables 0..n correspond to alpha, n..2n to alpha*
+        let sign = DVec::from_fn(2 * n, |i| if i < n { 1. } else { -1. });
+        let c = DVec::from_elem(2 * n, self.c);
+
+        let (p, alpha, nu) = match self.loss {
+            SVRLoss::Epsilon(eps) => {
+                let p = DVec::from_fn(2 * n, |i| if i < n { eps - y[i] } else { eps + y[i - n] });
+                (p, DVec::from_elem(2 * n, 0.), false)
+            },
+            SVRLoss::Nu(nu) => {
+                let p = DVec::from_fn(2 * n, |i| if i < n { - y[i] } else { y[i - n] });
+                // initial alpha must satisfy sum(alpha + alpha*) = C nu n
+                let mut alpha = DVec::from_elem(2 * n, 0.);
+                let mut sum = self.c * nu * (n as f64) / 2.;
+                for i in 0..n {
+                    let a = sum.min(self.c);
+                    alpha[i] = a;
+                    alpha[i + n] = a;
+                    sum -= a;
+                }
+                (p, alpha, true)
+            }
+        };
+
+        let (alpha, rho, r) = {
+            let q = |i: usize, j: usize| sign[i] * sign[j] * self.kernel(i % n, j % n);
+            solve_smo(&q, &p, &sign, &c, alpha, self.tolerance, self.max_iter, nu)
+        };
+
+        self.coef = DVec::from_fn(n, |i| alpha[i] - alpha[i + n]);
+        self.b = - rho;
+        self.epsilon = match self.loss {
+            SVRLoss::Epsilon(eps) => eps,
+            SVRLoss::Nu(_) => - r
+        };
+    }
+
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<f64> {
+        return DVec::from_fn(data.nrows(), |x| {
+            let row = data.row_slice(x, 0, data.ncols());
+            let mut v = self.b;
+            for i in 0..self.coef.len() {
+                if self.coef[i] != 0. {
+                    let xi = self.data.row_slice(i, 0, self.data.ncols());
+                    v += self.coef[i] * self.kernel_value(&xi, &row);
+                }
+            }
+            v
+        });
+    }
+}
+
+/// One-class SVM (Schölkopf et al.) for novelty detection
+///
+/// The linear kernel gives a hyperplane separating the records from the origin,
+/// so points far from the records on the other side are still inliers. Use the RBF
+/// kernel to learn a boundary around the records.
+pub struct OneClassSVM {
+    nu: f64,
+    tolerance: f64,
+    max_iter: usize,
+    kernels: DMat<f64>,
+
+    pub kernel_type: Kernel,
+
+    data: DMat<f64>,
+
+    pub alpha: DVec<f64>,
+    pub rho: f64,
+}
+
+impl SVMTrait for OneClassSVM {
+
+    /// nu is the upper bound of the fraction of outliers, used instead of C
+    fn new(nu: f64, tolerance: f64, max_iter: usize) -> OneClassSVM {
+        OneClassSVM {
+            nu: nu,
+            tolerance: tolerance,
+            max_iter: max_iter,
+            kernels: DMat::from_elem(1, 1, 0.0),
+
+            kernel_type: Kernel::Linear,
+
+            data: DMat::from_elem(1, 1, 0.0),
+
+            alpha: DVec::from_elem(1, 0.0),
+            rho: 0.0,
+        }
+    }
+
+    fn kernel(&self, i: usize, j: usize) -> f64 {
+        if i >= j {
+            return *self.kernels.index((i, j));
+        } else {
+            return *self.kernels.index((j, i));
+        }
+    }
+
+    fn kernel_value(&self, x1: &DVec<f64>, x2: &DVec<f64>) -> f64 {
+        return kernel_function(&self.kernel_type, x1, x2);
+    }
+}
+
+impl OneClassSVM {
+
+    pub fn fit(&mut self, data: &DMat<f64>) {
+        // build kernel matrix
+        self.kernels = self.get_kernel_matrix(&data);
+        self.data = dmat_copy(data);
+
+        let n = data.nrows();
+
+        // initial alpha must satisfy sum(alpha) = nu n, 0 <= alpha <= 1
+        let mut alpha = DVec::from_elem(n, 0.);
+        let mut sum = self.nu * (n as f64);
+        for i in 0..n {
+            alpha[i] = sum.min(1.);
+            sum -= alpha[i];
+        }
+
+        let y = DVec::from_elem(n, 1.);
+        let c = DVec::from_elem(n, 1.);
+        let p = DVec::from_elem(n, 0.);
+
+        let (alpha, rho, _) = {
+            let q = |i: usize, j: usize| self.kernel(i, j);
+            solve_smo(&q, &p, &y, &c, alpha, self.tolerance, self.max_iter, false)
+        };
+        self.alpha = alpha;
+        self.rho = rho;
+    }
+
+    /// signed distance to the boundary, positive for inliers
+    pub fn decision_function(&self, data: &DMat<f64>) -> DVec<f64> {
+        return DVec::from_fn(data.nrows(), |x| {
+            let row = data.row_slice(x, 0, data.ncols());
+            let mut v = - self.rho;
+            for i in 0..self.alpha.len() {
+                if self.alpha[i] != 0. {
+                    let xi = self.data.row_slice(i, 0, self.data.ncols());
+                    v += self.alpha[i] * self.kernel_value(&xi, &row);
+                }
+            }
+            v
+        });
+    }
+
+    /// 1 for inliers, -1 for outliers
+    pub fn predict(&self, data: &DMat<f64>) -> DVec<f64> {
+        let dist = self.decision_function(data);
+        return DVec::from_fn(dist.len(), |i| if dist[i] >= 0. { 1. } else { -1. });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DVec, DMat};
+    use super::{SVC, SVC2, MultiSVC, SVR, SVRLoss, OneClassSVM, SVMTrait, ClassWeight, Kernel,
+                get_sample_c, multiclass_probability};
+
+    #[test]
+    fn test_svr() {
+        // y = 2x + 1
+        let x: DMat<f64> = DMat::from_fn(10, 1, |i, _| i as f64);
+        let y: DVec<f64> = DVec::from_fn(10, |i| 2. * (i as f64) + 1.);
+
+        let mut svr = SVR::new(100., 0.001, 100000);
+        svr.fit(&x, &y);
+        let predicted = svr.predict(&x);
+        for i in 0..y.len() {
+            assert!((predicted[i] - y[i]).abs() < 0.2);
+        }
+
+        let mut svr = SVR::new(100., 0.001, 100000);
+        svr.loss = SVRLoss::Nu(0.5);
+        svr.fit(&x, &y);
+        assert!(svr.epsilon >= 0.);
+        let predicted = svr.predict(&x);
+        for i in 0..y.len() {
+            assert!((predicted[i] - y[i]).abs() < 0.2);
+        }
+    }
+
+    #[test]
+    fn test_svr_rbf() {
+        // y = sin(x) can't be fitted by the linear kernel
+        let x: DMat<f64> = DMat::from_fn(30, 1, |i, _| i as f64 * 0.2);
+        let y: DVec<f64> = DVec::from_fn(30, |i| (i as f64 * 0.2).sin());
+
+        let mut svr = SVR::new(10., 0.001, 100000);
+        svr.kernel_type = Kernel::RBF(1.);
+        svr.fit(&x, &y);
+        let predicted = svr.predict(&x);
+        for i in 0..y.len() {
+            assert!((predicted[i] - y[i]).abs() < 0.1 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_one_class_svm() {
+        let data: DMat<f64> = DMat::from_fn(20, 2, |i, j| 4. + ((i * (j + 3) + j) % 5) as f64 * 0.5);
+
+        let mut svm = OneClassSVM::new(0.2, 0.001, 10000);
+        svm.fit(&data);
+
+        // fraction of outliers in training data is at most nu
+        let predicted = svm.predict(&data);
+        let noutliers = (0..20).filter(|&i| predicted[i] == -1.).count();
+        assert!(noutliers as f64 / 20. <= 0.2 + 1e-10);
+
+        // center of the data is an inlier, points on the side of the origin are outliers
+        let x: DMat<f64> = DMat::from_row_vec(3, 2, &vec![5., 5., 0., 0., -10., -10.]);
+        let dist = svm.decision_function(&x);
+        assert!(dist[0] > 0.);
+        assert!(dist[1] < 0.);
+        assert!(dist[2] < dist[1]);
+        assert_eq!(svm.predict(&x), DVec::from_slice(3, &vec![1., -1., -1.]));
+
+        // linear kernel can't detect points far from the origin
+        let far: DMat<f64> = DMat::from_row_vec(1, 2, &vec![100., 100.]);
+        assert_eq!(svm.predict(&far), DVec::from_slice(1, &vec![1.]));
+    }
+
+    #[test]
+    fn test_one_class_svm_rbf() {
+        // 5 x 5 grid from (4, 4) to (6, 6)
+        let data: DMat<f64> = DMat::from_fn(25, 2, |i, j| {
+            4. + (if j == 0 { i % 5 } else { i / 5 }) as f64 * 0.5
+        });
+
+        let mut svm = OneClassSVM::new(0.2, 0.001, 10000);
+        svm.kernel_type = Kernel::RBF(0.5);
+        svm.fit(&data);
+
+        // support vectors on the boundary are within the tolerance, corners are outliers
+        let dist = svm.decision_function(&data);
+        let noutliers = (0..25).filter(|&i| dist[i] < -0.001).count();
+        assert_eq!(noutliers, 4);
+        assert!(noutliers as f64 / 25. <= 0.2);
+
+        // outliers on both sides of the records
+        let x: DMat<f64> = DMat::from_row_vec(5, 2, &vec![5., 5., 0., 0., 10., 10.,
+                                                         100., 100., 5., -5.]);
+        let dist = svm.decision_function(&x);
+        assert!(dist[0] > 0.);
+        assert!(dist[3] < 0.);
+        assert_eq!(svm.predict(&x), DVec::from_slice(5, &vec![1., -1., -1., -1., -1.]));
+    }
+
+    #[test]
+    fn test_sample_c() {
+        let y: DVec<f64> = DVec::from_slice(4, &vec![1., -1., -1., -1.]);
+        let w: DVec<f64> = DVec::from_slice(4, &vec![1., 1., 1., 0.5]);
+
+        let exp: DVec<f64> = DVec::from_slice(4, &vec![10., 10., 10., 5.]);
+        assert_eq!(exp, get_sample_c(10., &ClassWeight::Uniform, &y, &w));
+
+        let exp: DVec<f64> = DVec::from_slice(4, &vec![20., 20. / 3., 20. / 3., 10. / 3.]);
+        let res = get_sample_c(10., &ClassWeight::Balanced, &y, &w);
+        for i in 0..4 {
+            assert!((exp[i] - res[i]).abs() < 1e-10);
+        }
+
+        let cw = ClassWeight::Manual { negative: 1., positive: 200. };
+        let exp: DVec<f64> = DVec::from_slice(4, &vec![2000., 10., 10., 5.]);
+        assert_eq!(exp, get_sample_c(10., &cw, &y, &w));
+    }
+
+    #[test]
+    fn test_svc_weights() {
+        // 13 negatives and 3 positives, the positive at 5 overlaps with negatives
+        let xs = vec![0., 0.5, 1., 1.5, 2., 2.5, 3., 3.5, 4., 4.5, 5., 5.5, 6., 5., 6.5, 7.];
+        let data: DMat<f64> = DMat::from_col_vec(16, 1, &xs);
+        let y: DVec<f64> = DVec::from_fn(16, |i| if i < 13 { -1. } else { 1. });
+        let weights = vec![ClassWeight::Uniform, ClassWeight::Balanced,
+                           ClassWeight::Manual { negative: 1., positive: 10. }];
+
+        // weighting the minority class moves the boundary toward negatives
+        let mut bs = vec![];
+        let mut npositives = vec![];
+        for cw in &weights {
+            let mut svc = SVC2::new(1., 0.001, 1000);
+            svc.class_weight = cw.clone();
+            svc.fit(&data, &y);
+            bs.push(svc.b);
+            npositives.push(svc.predict(&data).at.iter().filter(|&v| *v == 1.).count());
+        }
+        assert!(bs[1] > bs[0] + 0.1 && bs[2] > bs[0] + 0.1);
+        assert!(npositives[1] > npositives[0] && npositives[2] > npositives[0]);
+
+        let mut bs = vec![];
+        for cw in &weights {
+            let mut svc = SVC::new(1., 0.001, 1000);
+            svc.class_weight = cw.clone();
+            svc.seed = Some(1);
+            svc.fit(&data, &y);
+            bs.push(svc.b);
+        }
+        assert!(bs[1] > bs[0] + 0.1 && bs[2] > bs[0] + 0.1);
+
+        // record with weight 0 is ignored
+        let mut sample_weight = DVec::from_elem(16, 1.);
+        sample_weight[13] = 0.;
+        let mut svc2 = SVC2::new(1., 0.001, 1000);
+        svc2.fit(&data, &y);
+        assert!(svc2.alpha[13] > 0.);
+        svc2.fit_with_weights(&data, &y, &sample_weight);
+        assert_eq!(svc2.alpha[13], 0.);
+
+        let mut svc = SVC::new(1., 0.001, 1000);
+        svc.seed = Some(1);
+        svc.fit(&data, &y);
+        assert!(svc.alpha[13] > 0.);
+        svc.fit_with_weights(&data, &y, &sample_weight);
+        assert_eq!(svc.alpha[13], 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "requires both labels")]
+    fn test_sample_c_balanced_one_label() {
+        let y: DVec<f64> = DVec::from_elem(3, 1.);
+        let w: DVec<f64> = DVec::from_elem(3, 1.);
+        get_sample_c(10., &ClassWeight::Balanced, &y, &w);
+    }
+
+    #[test]
+    fn test_multiclass_probability() {
+        // equal pairwise probabilities
+        let r: DMat<f64> = DMat::from_elem(3, 3, 0.5);
+        let p = multiclass_probability(&r);
+        for i in 0..3 {
+            assert!((p[i] - 1. / 3.).abs() < 1e-6);
+        }
+
+        // class 0 wins against all others
+        let r: DMat<f64> = DMat::from_row_vec(3, 3, &vec![0.0, 0.9, 0.9,
+                                                          0.1, 0.0, 0.5,
+                                                          0.1, 0.5, 0.0]);
+        let p = multiclass_probability(&r);
+        assert!((p[0] + p[1] + p[2] - 1.).abs() < 1e-6);
+        assert!(p[0] > p[1] && p[0] > p[2]);
+    }
+
+    #[test]
+    fn test_svc_seed() {
+        let data: DMat<f64> = DMat::from_fn(20, 2, |i, j| {
+            ((i * (j + 2)) % 5) as f64 * 0.3 + if i < 10 { 1. } else { -1. }
+        });
+        let y: DVec<f64> = DVec::from_fn(20, |i| if i < 10 { 1. } else { -1. });
+
+        let fit_svc = |seed: usize| {
+            let mut svc = SVC::new(1., 0.001, 1000);
+            svc.seed = Some(seed);
+            svc.fit(&data, &y);
+            svc
+        };
+        let (svc1, svc2) = (fit_svc(1), fit_svc(1));
+        assert_eq!(svc1.alpha, svc2.alpha);
+        assert_eq!(svc1.b, svc2.b);
+        // another seed may start from another pair, but the fit must be valid
+        let svc3 = fit_svc(2);
+        assert_eq!(svc3.alpha.len(), 20);
+        assert!(svc3.b.is_finite());
+
+        let fit_svc2 = |seed: usize| {
+            let mut svc = SVC2::new(1., 0.001, 1000);
+            svc.probability = true;
+            svc.seed = Some(seed);
+            svc.fit(&data, &y);
+            svc
+        };
+        let (svc1, svc2) = (fit_svc2(1), fit_svc2(1));
+        assert_eq!(svc1.alpha, svc2.alpha);
+        assert_eq!(svc1.b, svc2.b);
+        assert_eq!(svc1.prob, svc2.prob);
+        // another seed splits other folds for probability
+        let svc3 = fit_svc2(2);
+        assert_eq!(svc3.alpha, svc1.alpha);
+        let (a, b) = svc3.prob.unwrap();
+        assert!(a.is_finite() && b.is_finite());
+    }
+
+    /// check that rows are probabilities and the most probable class is the predicted label
+    fn check_proba(proba: &DMat<f64>, predicted: &DVec<f64>, classes: &[f64]) {
+        for i in 0..proba.nrows() {
+            let mut total = 0.;
+            let mut best = 0;
+            for j in 0..proba.ncols() {
+                assert!(0. <= proba[(i, j)] && proba[(i, j)] <= 1.);
+                total += proba[(i, j)];
+                if proba[(i, j)] > proba[(i, best)] {
+                    best = j;
+                }
+            }
+            assert!((total - 1.).abs() < 1e-6);
+            assert_eq!(classes[best], predicted[i]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "SVC2 must be fitted with probability = true")]
+    fn test_predict_proba_not_fitted() {
+        let data: DMat<f64> = DMat::from_row_vec(4, 1, &vec![0., 1., 3., 4.]);
+        let y: DVec<f64> = DVec::from_slice(4, &vec![-1., -1., 1., 1.]);
+        let mut svc = SVC2::new(1., 0.001, 1000);
+        svc.fit(&data, &y);
+        // sigmoid is not fitted
+        svc.probability = true;
+        svc.predict_proba(&data);
+    }
+
+    #[test]
+    #[should_panic(expected = "MultiSVC must be fitted with probability = true")]
+    fn test_multiclass_predict_proba_not_fitted() {
+        let data: DMat<f64> = DMat::from_row_vec(6, 1, &vec![0., 1., 5., 6., 10., 11.]);
+        let y: DVec<f64> = DVec::from_slice(6, &vec![0., 0., 1., 1., 2., 2.]);
+        let mut svc = MultiSVC::new(1., 0.001, 1000);
+        svc.fit(&data, &y);
+        svc.probability = true;
+        svc.predict_proba(&data);
+    }
+
+    #[test]
+    fn test_predict_proba() {
+        // separable 2 groups
+        let data: DMat<f64> = DMat::from_fn(20, 2, |i, j| {
+            ((i * (j + 2)) % 5) as f64 * 0.3 + if i < 10 { 2. } else { -2. }
+        });
+        let y: DVec<f64> = DVec::from_fn(20, |i| if i < 10 { 1. } else { -1. });
+
+        let mut svc = SVC2::new(1., 0.001, 1000);
+        svc.probability = true;
+        svc.seed = Some(1);
+        svc.fit(&data, &y);
+        let predicted = svc.predict(&data);
+        assert_eq!(predicted, y);
+        // probability of label 1 increases with the decision value
+        assert!(svc.prob.unwrap().0 < 0.);
+        check_proba(&svc.predict_proba(&data), &predicted, &[-1., 1.]);
+
+        // 3 groups
+        let data: DMat<f64> = DMat::from_fn(30, 2, |i, j| {
+            ((i * (j + 2)) % 5) as f64 * 0.3 + match (i / 10, j) {
+                (0, _) => 3.,
+                (1, 0) => -3.,
+                (1, _) => 3.,
+                _ => -3.
+            }
+        });
+        let y: DVec<f64> = DVec::from_fn(30, |i| (i / 10) as f64);
+
+        let mut svc = MultiSVC::new(1., 0.001, 1000);
+        svc.probability = true;
+        svc.seed = Some(1);
+        svc.fit(&data, &y);
+        let predicted = svc.predict(&data);
+        assert_eq!(predicted, y);
+        check_proba(&svc.predict_proba(&data), &predicted, &[0., 1., 2.]);
+    }
+}